@@ -2,6 +2,25 @@
 
 use bytemuck::{Pod, Zeroable};
 
+/// The I/O bypassed the page cache (`O_DIRECT` / `IOCB_DIRECT`).
+pub const EVENT_FLAG_DIRECT: u16 = 1 << 0;
+/// The mmap fault had to read the page from disk (`VM_FAULT_MAJOR`).
+pub const EVENT_FLAG_FAULT_MAJOR: u16 = 1 << 1;
+/// The mmap fault dropped the mmap lock and will be retried (`VM_FAULT_RETRY`).
+pub const EVENT_FLAG_FAULT_RETRY: u16 = 1 << 2;
+/// The mmap fault failed (`VM_FAULT_ERROR`).
+pub const EVENT_FLAG_FAULT_ERROR: u16 = 1 << 3;
+/// Write fault on a shared mapping notifying the filesystem (`page_mkwrite`).
+pub const EVENT_FLAG_FAULT_MKWRITE: u16 = 1 << 4;
+/// The mmap fault was a write fault (`FAULT_FLAG_WRITE`), the only hint at the direction of
+/// `NullPage` events.
+pub const EVENT_FLAG_FAULT_WRITE: u16 = 1 << 5;
+/// The block request completed with an error status.
+pub const EVENT_FLAG_RQ_ERROR: u16 = 1 << 6;
+
+/// Longest path delivered with an event, matches the kernel's `PATH_MAX`.
+pub const PATH_MAX: usize = 4096;
+
 #[repr(u32)]
 #[derive(Clone, Copy, Zeroable, Debug)]
 pub enum EventType {
@@ -14,15 +33,80 @@ pub enum EventType {
 
     VfsReadv = 40,
     VfsWritev = 50,
+
+    VfsFsync = 60,
+    VfsFdatasync = 61,
+    SyncFileRange = 70,
+
+    VfsUnlink = 100,
+    VfsRename = 101,
+    VfsMkdir = 102,
+    VfsRmdir = 103,
+    DoTruncate = 104,
+    VfsFallocate = 105,
+
+    VfsGetattr = 110,
+    IterateDir = 111,
+    VfsGetxattr = 112,
+    VfsSetxattr = 113,
+
+    IoUringRead = 120,
+    IoUringWrite = 121,
+
+    AioRead = 130,
+    AioWrite = 131,
+    VfsIocbRead = 132,
+    VfsIocbWrite = 133,
+
+    BlockRqRead = 140,
+    BlockRqWrite = 141,
+
+    NfsRead = 150,
+    NfsWrite = 151,
 }
 
 unsafe impl bytemuck::Pod for EventType {}
 
+/// How far the path of an event could be resolved.
+#[repr(u8)]
+#[derive(Clone, Copy, Zeroable, Debug, PartialEq, Eq)]
+pub enum PathStatus {
+    /// No path, e.g. the file has no dentry or resolution failed.
+    Unresolved = 0,
+    Complete = 1,
+    /// Leading components are missing, the path was longer than `PATH_MAX`.
+    Truncated = 2,
+    /// The file was unlinked, the path is the one it had last.
+    Deleted = 3,
+    /// The file was never linked into a directory, the path is a pseudo name such as
+    /// `memfd:<name>` or `anon_inode:[eventfd]`.
+    Anonymous = 4,
+}
+
+unsafe impl bytemuck::Pod for PathStatus {}
+
+/// Offset of a read or write relative to where the previous one on the same file by the same
+/// process ended.
+#[repr(u8)]
+#[derive(Clone, Copy, Zeroable, Debug, PartialEq, Eq)]
+pub enum AccessPattern {
+    /// Not a positional read or write, e.g. an mmap fault or a pipe.
+    Unknown = 0,
+    /// Continues where the previous access ended, or starts at the beginning of the file.
+    Sequential = 1,
+    Random = 2,
+    /// Goes back to the beginning of the file.
+    Rewind = 3,
+}
+
+unsafe impl bytemuck::Pod for AccessPattern {}
+
 #[repr(C)]
 #[derive(Clone, Copy, Zeroable, Pod, Debug)]
 pub struct FileAccessEvent {
     pub inode: u64,
     pub bytes: u64,
+    pub duration_ns: u64,
 
     pub tid: u32,
     pub tgid: u32,
@@ -33,7 +117,132 @@ pub struct FileAccessEvent {
 
     pub s_magic: u64,
     pub i_mode: u16,
-    _pad1: [u8; 6],
+    /// `EVENT_FLAG_*` bits.
+    pub flags: u16,
+    /// Number of calls this event stands for when the probe is sampled, 0 means 1.
+    pub weight: u32,
+
+    /// Length of the path following the event in its ring buffer record, not NUL terminated.
+    pub path_len: u16,
+    pub path_status: PathStatus,
+    pub _pad1: u8,
+    /// Inode number of the mount namespace the path was resolved in, 0 if unknown.
+    pub mnt_ns: u32,
+
+    /// File backing an overlayfs file in its upper or lower dir, zero for other filesystems.
+    pub real_inode: u64,
+    pub real_s_magic: u64,
+    pub real_dev: u32,
+    /// Overlayfs layer of the real file plus one: 1 is the upper dir, 2 the first lower dir.
+    pub ovl_layer: u8,
+    pub pattern: AccessPattern,
+    pub _pad2: [u8; 2],
+
+    /// Effective uid and gid of the caller.
+    pub uid: u32,
+    pub gid: u32,
+    /// Owner of the file.
+    pub i_uid: u32,
+    pub i_gid: u32,
+
+    /// Parent of the calling process.
+    pub ppid: u32,
+    pub _pad3: [u8; 4],
+    /// `start_boottime` of the calling process, tells a reused tgid from the previous one.
+    pub start_time: u64,
+    /// cgroup v2 id of the calling task.
+    pub cgroup_id: u64,
+    /// File offset of reads and writes.
+    pub pos: u64,
+    /// Time a block request spent queued before it was issued to the driver.
+    pub queue_ns: u64,
+}
+
+/// Namespace operation (unlink, rename, mkdir, ...) delivered through `META_EVENTS`.
+///
+/// The record continues with the affected path (`event.path_len` bytes) and the new path
+/// (`new_path_len` bytes), which is only set for renames.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug)]
+pub struct MetaOpEvent {
+    pub event: FileAccessEvent,
+    pub new_path_len: u16,
+    pub new_path_status: PathStatus,
+    pub _pad1: [u8; 5],
+}
+
+/// Key of the per-file `PAGE_CACHE_STATS` map.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, PartialEq, Eq, Hash)]
+pub struct PageCacheKey {
+    pub inode: u64,
+    pub dev: u32,
+    _pad1: u32,
+}
 
-    pub path: [u8; 256],
+impl PageCacheKey {
+    pub const fn new(dev: u32, inode: u64) -> Self {
+        Self {
+            inode,
+            dev,
+            _pad1: 0,
+        }
+    }
 }
+
+/// Page counters of a single file, only ever incremented by the eBPF side.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, Default)]
+pub struct PageCacheStats {
+    /// Pages marked accessed (`folio_mark_accessed`).
+    pub accessed: u64,
+    /// Pages inserted into the page cache (`filemap_add_folio`).
+    pub added: u64,
+    /// Pages requested by readahead (`page_cache_ra_unbounded`).
+    pub readahead: u64,
+}
+
+/// Key of the `WRITEBACK_STATS` map.
+///
+/// `cgroup_id` is the cgroup v2 id of the task that dirtied the pages, or of the cgroup owning
+/// the inode's writeback context for written back pages.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, PartialEq, Eq, Hash)]
+pub struct WritebackKey {
+    pub inode: u64,
+    pub cgroup_id: u64,
+    pub dev: u32,
+    _pad1: u32,
+}
+
+impl WritebackKey {
+    pub const fn new(dev: u32, inode: u64, cgroup_id: u64) -> Self {
+        Self {
+            inode,
+            cgroup_id,
+            dev,
+            _pad1: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, Default)]
+pub struct WritebackStats {
+    /// Bytes of clean pages turned dirty (`writeback_dirty_folio`).
+    pub dirtied_bytes: u64,
+    /// Bytes written back by the flusher (`writeback_single_inode`).
+    pub written_bytes: u64,
+}
+
+#[cfg(feature = "user")]
+unsafe impl aya::Pod for PageCacheKey {}
+
+#[cfg(feature = "user")]
+unsafe impl aya::Pod for PageCacheStats {}
+
+#[cfg(feature = "user")]
+unsafe impl aya::Pod for WritebackKey {}
+
+#[cfg(feature = "user")]
+unsafe impl aya::Pod for WritebackStats {}
@@ -0,0 +1,19 @@
+/* written by hand from include/uapi/linux/aio_abi.h (little-endian layout), no generated type
+ * refers to it */
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct iocb {
+    pub aio_data: u64_,
+    pub aio_key: u32_,
+    pub aio_rw_flags: ::aya_ebpf::cty::c_int,
+    pub aio_lio_opcode: u16_,
+    pub aio_reqprio: s16,
+    pub aio_fildes: u32_,
+    pub aio_buf: u64_,
+    pub aio_nbytes: u64_,
+    pub aio_offset: s64,
+    pub aio_reserved2: u64_,
+    pub aio_flags: u32_,
+    pub aio_resfd: u32_,
+}
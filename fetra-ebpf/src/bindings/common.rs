@@ -67980,3 +67980,15 @@ pub struct xsk_tx_metadata_ops {
     >,
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct renamedata {
+    pub old_mnt_idmap: *mut mnt_idmap,
+    pub old_dir: *mut inode,
+    pub old_dentry: *mut dentry,
+    pub new_mnt_idmap: *mut mnt_idmap,
+    pub new_dir: *mut inode,
+    pub new_dentry: *mut dentry,
+    pub delegated_inode: *mut *mut inode,
+    pub flags: ::aya_ebpf::cty::c_uint,
+}
@@ -2,6 +2,8 @@
 mod tmp {
     include!("./pageflags.rs");
     include!("./common.rs");
+    include!("./overlayfs.rs");
+    include!("./aio.rs");
 }
 
 pub use tmp::*;
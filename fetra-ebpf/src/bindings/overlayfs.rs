@@ -0,0 +1,39 @@
+/* written by hand from fs/overlayfs/ovl_entry.h, overlayfs is usually a module and its types
+ * are not in vmlinux BTF */
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ovl_layer {
+    pub mnt: *mut vfsmount,
+    pub trap: *mut inode,
+    pub fs: *mut ::aya_ebpf::cty::c_void,
+    pub idx: ::aya_ebpf::cty::c_int,
+    pub fsid: ::aya_ebpf::cty::c_int,
+    pub has_xwhiteouts: bool_,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ovl_path {
+    pub layer: *const ovl_layer,
+    pub dentry: *mut dentry,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct ovl_entry {
+    pub __numlower: ::aya_ebpf::cty::c_uint,
+    pub __lowerstack: __IncompleteArrayField<ovl_path>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ovl_inode {
+    pub __bindgen_anon_1: *mut ::aya_ebpf::cty::c_void,
+    pub redirect: *const ::aya_ebpf::cty::c_char,
+    pub version: u64_,
+    pub flags: ::aya_ebpf::cty::c_ulong,
+    pub vfs_inode: inode,
+    pub __upperdentry: *mut dentry,
+    pub oe: *mut ovl_entry,
+}
@@ -1,25 +1,59 @@
-use crate::bindings::{dentry, mount, path, qstr, task_struct, vfsmount};
-use aya_ebpf::helpers::{bpf_get_current_task_btf, bpf_probe_read_kernel_buf};
+use crate::bindings::{dentry, list_head, mnt_namespace, mount, path, qstr, task_struct, vfsmount};
+use aya_ebpf::helpers::{bpf_get_current_task_btf, bpf_loop, bpf_probe_read_kernel_buf};
 use core::ffi::c_void;
 use core::mem::offset_of;
 
 use crate::container_of_mut;
 use crate::ext::QstrExt;
+use crate::helpers::Scratch;
+use crate::HOST_MNT_NS;
 use aya_ebpf::{helpers::bpf_probe_read_kernel, macros::map, maps::PerCpuArray};
 use core::slice::from_raw_parts_mut;
+use fetra_common::PathStatus;
 
 pub const MAX_BUF_LEN: usize = 4096;
 pub const UNRESOLVED_PATH_COMPONENTS: i32 = 0x02;
 
 const MAX_NAME_LEN: usize = 0xff;
 
+const ANON_INODE_FS_MAGIC: u64 = 0x09041934;
+
+/// Mount instances of a superblock looked at when searching one in the host namespace.
+const MAX_SB_MOUNTS: usize = 16;
+
+/// Every component takes at least two bytes ("/a"), so a full buffer never needs more steps,
+/// plus one step per mount crossed on the way up.
+const MAX_RESOLVE_STEPS: u32 = (MAX_BUF_LEN / 2 + 64) as u32;
+
+/// Path built by [`d_path_local`], right-aligned in the per-CPU buffer.
+///
+/// Only valid until the next path is resolved in the same [`Scratch`] slot on this CPU.
+pub struct ResolvedPath {
+    pub buf: *const u8,
+    pub len: usize,
+    pub status: PathStatus,
+    /// Mount namespace of the task the path was resolved for.
+    pub mnt_ns: u32,
+}
+
+impl ResolvedPath {
+    pub const fn empty() -> Self {
+        Self {
+            buf: core::ptr::null(),
+            len: 0,
+            status: PathStatus::Unresolved,
+            mnt_ns: 0,
+        }
+    }
+}
+
 #[repr(C)]
 pub struct BufferHeap {
     pub buf: [u8; MAX_BUF_LEN + 256],
 }
 
 #[map(name = "BUFFER_HEAP_MAP")]
-static mut BUFFER_HEAP_MAP: PerCpuArray<BufferHeap> = PerCpuArray::with_max_entries(1, 0);
+static mut BUFFER_HEAP_MAP: PerCpuArray<BufferHeap> = PerCpuArray::with_max_entries(2, 0);
 
 #[inline(always)]
 unsafe fn read_mnt_parent(curr_vfsmnt: *mut vfsmount) -> Result<*mut mount, i64> {
@@ -37,6 +71,13 @@ unsafe fn is_root(dentry: *const dentry) -> Result<bool, i64> {
     Ok(dentry == parent)
 }
 
+/// `d_unlinked()`: the dentry was removed from the dcache hash by unlink or rename over it.
+#[inline(always)]
+unsafe fn is_unlinked(dentry: *const dentry) -> Result<bool, i64> {
+    let pprev = bpf_probe_read_kernel(&(*dentry).d_hash.pprev)?;
+    Ok(pprev.is_null() && !is_root(dentry)?)
+}
+
 struct ResolveContext {
     ctx: *mut c_void,
 
@@ -51,15 +92,44 @@ struct ResolveContext {
     base: *mut u8,
     buf_remainder: usize,
     resolved: bool,
+    truncated: bool,
+    error: i64,
+}
+
+/// `bpf_loop` callback, returns 1 to stop.
+unsafe extern "C" fn resolve_step(_index: u32, ctx: *mut c_void) -> i64 {
+    let resolver = &mut *(ctx as *mut ResolveContext);
+    match resolver.step() {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            resolver.error = e;
+            1
+        }
+    }
 }
 
 impl ResolveContext {
+    /// Walks up with `bpf_loop`, which the verifier checks once instead of per iteration, so the
+    /// depth is only bounded by the buffer. Available since 5.17, well below what the other
+    /// hooks need.
     unsafe fn resolve(&mut self) -> Result<(), i64> {
-        for _ in 0..32 {
-            if !self.step()? {
-                // info!(self.ctx, "Bailing on step {}", i);
-                break;
-            }
+        let ret = bpf_loop(
+            MAX_RESOLVE_STEPS,
+            resolve_step as *mut c_void,
+            self as *mut Self as *mut c_void,
+            0,
+        );
+        if ret < 0 {
+            return Err(ret);
+        }
+        if self.error != 0 {
+            return Err(self.error);
+        }
+
+        // ran out of buffer or steps before reaching the root
+        if !self.is_resolved() {
+            self.truncated = true;
         }
 
         Ok(())
@@ -68,7 +138,11 @@ impl ResolveContext {
     unsafe fn step(&mut self) -> Result<bool, i64> {
         if self.is_resolved() {
             self.resolved = true;
-            return Ok(true);
+            return Ok(false);
+        }
+
+        if self.buf_remainder == 0 {
+            return Ok(false);
         }
 
         let curr_mnt_root = bpf_probe_read_kernel(&(*self.curr_vfsmnt).mnt_root)?;
@@ -88,7 +162,7 @@ impl ResolveContext {
         }
 
         let name = bpf_probe_read_kernel(&(*self.curr_dentry).d_name)?;
-        self.prepend_name(name)?;
+        self.prepend_name(name, true)?;
 
         // info!(self.ctx, "name_len: {}", name.len());
 
@@ -97,16 +171,17 @@ impl ResolveContext {
         Ok(true)
     }
 
-    unsafe fn prepend_name(&mut self, name: qstr) -> Result<(), i64> {
+    unsafe fn prepend_name(&mut self, name: qstr, slash: bool) -> Result<(), i64> {
         let mut name_len = name.len() as usize & MAX_NAME_LEN;
         let mut name_ptr = name.name as *mut u8;
-        let mut write_slash = true;
+        let mut write_slash = slash;
 
-        if name_len > self.buf_remainder {
+        if name_len + write_slash as usize > self.buf_remainder {
             let trim = name_len - self.buf_remainder;
             name_ptr = name_ptr.wrapping_add(trim);
             name_len = self.buf_remainder;
             write_slash = false;
+            self.truncated = true;
         }
 
         let space_needed = name_len + write_slash as usize;
@@ -126,27 +201,170 @@ impl ResolveContext {
         Ok(())
     }
 
+    /// Prepends a short constant, dropping it when the buffer is full.
+    unsafe fn prepend_bytes(&mut self, bytes: &[u8]) {
+        if bytes.len() > self.buf_remainder {
+            self.truncated = true;
+            return;
+        }
+
+        self.buf_remainder -= bytes.len();
+        for (i, b) in bytes.iter().enumerate() {
+            *self.base.add(self.buf_remainder + i) = *b;
+        }
+    }
+
+    /// Names files that were never linked into a directory the way `/proc/<pid>/fd` shows them:
+    /// memfds already carry a `memfd:` prefix in their dentry name, anon inodes get one.
+    unsafe fn resolve_pseudo(&mut self) -> Result<(), i64> {
+        let name = bpf_probe_read_kernel(&(*self.curr_dentry).d_name)?;
+        self.prepend_name(name, false)?;
+
+        let sb = bpf_probe_read_kernel(&(*self.curr_dentry).d_sb)?;
+        if bpf_probe_read_kernel(&(*sb).s_magic)? == ANON_INODE_FS_MAGIC {
+            self.prepend_bytes(b"anon_inode:");
+        }
+
+        self.resolved = true;
+        Ok(())
+    }
+
     fn is_resolved(&self) -> bool {
         self.resolved
             || (self.root_vfsmnt == self.curr_vfsmnt && self.root_dentry == self.curr_dentry)
     }
 }
 
-pub unsafe fn d_path_local(ctx: *mut c_void, path: path) -> Result<(*mut u8, usize), i64> {
-    let Some(heap) = BUFFER_HEAP_MAP.get_ptr_mut(0) else {
+#[inline(always)]
+unsafe fn mnt_ns_inum(ns: *const mnt_namespace) -> Result<u32, i64> {
+    if ns.is_null() {
+        return Ok(0);
+    }
+    bpf_probe_read_kernel(&(*ns).ns.inum)
+}
+
+/// Finds an instance of the mount `vfsmnt` in the host mount namespace.
+///
+/// Container mounts are copies whose parents end at the container root, but the host usually
+/// has the same superblock mounted too (the overlayfs `merged` dir, the volume's backing fs).
+/// An instance with the same root, or with the superblock root, can resolve any dentry below
+/// `vfsmnt`. Mounts without a host instance are resolved in their own namespace.
+unsafe fn host_mount(vfsmnt: *mut vfsmount, host_mnt_ns: u32) -> Result<*mut vfsmount, i64> {
+    let mount_ptr: *mut mount = container_of_mut!(vfsmnt, mount, mnt);
+    if mnt_ns_inum(bpf_probe_read_kernel(&(*mount_ptr).mnt_ns)?)? == host_mnt_ns {
+        return Ok(vfsmnt);
+    }
+
+    let mnt_root = bpf_probe_read_kernel(&(*vfsmnt).mnt_root)?;
+    let sb = bpf_probe_read_kernel(&(*vfsmnt).mnt_sb)?;
+    let sb_root = bpf_probe_read_kernel(&(*sb).s_root)?;
+
+    let head = &(*sb).s_mounts as *const list_head as *mut list_head;
+    let mut node = bpf_probe_read_kernel(&(*head).next)?;
+    for _ in 0..MAX_SB_MOUNTS {
+        if node == head {
+            break;
+        }
+
+        let candidate: *mut mount = container_of_mut!(node, mount, mnt_instance);
+        let candidate_root = bpf_probe_read_kernel(&(*candidate).mnt.mnt_root)?;
+        if (candidate_root == mnt_root || candidate_root == sb_root)
+            && mnt_ns_inum(bpf_probe_read_kernel(&(*candidate).mnt_ns)?)? == host_mnt_ns
+        {
+            return Ok((&mut (*candidate).mnt) as *mut vfsmount);
+        }
+
+        node = bpf_probe_read_kernel(&(*node).next)?;
+    }
+
+    Ok(vfsmnt)
+}
+
+/// Builds a `path` for a bare dentry using the first mount of its superblock.
+///
+/// Namespace operations (`vfs_unlink`, `vfs_mkdir`, ...) don't get a `vfsmount`, so for bind
+/// mounts the path is resolved relative to whichever mount instance was created first.
+pub unsafe fn dentry_path(dentry: *const dentry) -> Result<path, i64> {
+    let sb = bpf_probe_read_kernel(&(*dentry).d_sb)?;
+    let head = &(*sb).s_mounts as *const list_head as *mut list_head;
+    let first = bpf_probe_read_kernel(&(*head).next)?;
+    if first == head {
         return Err(-1);
-    };
-    let base = heap as *mut u8;
+    }
+
+    let mount_ptr: *mut mount = container_of_mut!(first, mount, mnt_instance);
+
+    Ok(path {
+        mnt: (&mut (*mount_ptr).mnt) as *mut vfsmount,
+        dentry: dentry as *mut dentry,
+    })
+}
+
+/// Root directory of a task that paths are resolved against, and the mount namespace they are
+/// reported in. Captured up front where paths are only resolved later, in another context.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PathRoot {
+    root: path,
+    mnt_ns: u32,
+}
+
+impl PathRoot {
+    pub unsafe fn current() -> Result<Self, i64> {
+        let task = bpf_get_current_task_btf() as *mut task_struct;
+        Ok(Self {
+            root: (*(*task).fs).root,
+            mnt_ns: mnt_ns_inum((*(*task).nsproxy).mnt_ns)?,
+        })
+    }
+}
 
-    let task = bpf_get_current_task_btf() as *mut task_struct;
-    let fs = (*task).fs;
-    let root = (*fs).root;
+/// Resolves `path` relative to the root of the current task, or to the host root when
+/// `HOST_MNT_NS` is set.
+///
+/// Resolution errors don't fail the event, they are reported as [`PathStatus::Unresolved`].
+pub unsafe fn d_path_local(ctx: *mut c_void, path: path) -> ResolvedPath {
+    match PathRoot::current() {
+        Ok(root) => d_path_from(ctx, path, &root, Scratch::Task),
+        Err(_) => ResolvedPath::empty(),
+    }
+}
 
-    let root_dentry = root.dentry;
-    let root_vfsmnt = root.mnt;
+/// Like [`d_path_local`], relative to the root of the task `root` was captured from and built
+/// in the `scratch` slot.
+pub unsafe fn d_path_from(
+    ctx: *mut c_void,
+    path: path,
+    root: &PathRoot,
+    scratch: Scratch,
+) -> ResolvedPath {
+    try_d_path_from(ctx, path, root, scratch).unwrap_or(ResolvedPath::empty())
+}
 
+unsafe fn try_d_path_from(
+    ctx: *mut c_void,
+    path: path,
+    root: &PathRoot,
+    scratch: Scratch,
+) -> Result<ResolvedPath, i64> {
+    let Some(heap) = BUFFER_HEAP_MAP.get_ptr_mut(scratch as u32) else {
+        return Err(-1);
+    };
+    let base = heap as *mut u8;
+
+    let mnt_ns = root.mnt_ns;
+    let host_mnt_ns = HOST_MNT_NS;
     let curr_dentry = path.dentry;
-    let curr_vfsmnt = path.mnt;
+    let (root_dentry, root_vfsmnt, curr_vfsmnt) = if host_mnt_ns != 0 {
+        // no root to stop at, the walk ends at the top of the host mount tree
+        (
+            core::ptr::null_mut(),
+            core::ptr::null_mut(),
+            host_mount(path.mnt, host_mnt_ns)?,
+        )
+    } else {
+        (root.root.dentry, root.root.mnt, path.mnt)
+    };
 
     let mount_ptr: *mut mount = container_of_mut!(curr_vfsmnt, mount, mnt);
 
@@ -162,15 +380,37 @@ pub unsafe fn d_path_local(ctx: *mut c_void, path: path) -> Result<(*mut u8, usi
         base,
         buf_remainder: MAX_BUF_LEN,
         resolved: false,
+        truncated: false,
+        error: 0,
     };
 
-    resolver_context.resolve()?;
+    // alloc_file_pseudo() dentries (memfd, anon inodes) are their own parent below the mount
+    // root and are reachable from no directory
+    let mnt_root = bpf_probe_read_kernel(&(*curr_vfsmnt).mnt_root)?;
+    if curr_dentry != mnt_root && is_root(curr_dentry)? {
+        resolver_context.resolve_pseudo()?;
+        return Ok(ResolvedPath {
+            buf: base.add(resolver_context.buf_remainder),
+            len: MAX_BUF_LEN - resolver_context.buf_remainder,
+            status: PathStatus::Anonymous,
+            mnt_ns,
+        });
+    }
 
-    // let ctx = FEntryContext::new(ctx);
-    // info!(&ctx, "buf_remainder: {}", resolver_context.buf_remainder);
+    resolver_context.resolve()?;
 
-    let start = base.add(resolver_context.buf_remainder);
-    let len = MAX_BUF_LEN - resolver_context.buf_remainder;
+    let status = if is_unlinked(path.dentry)? {
+        PathStatus::Deleted
+    } else if resolver_context.truncated {
+        PathStatus::Truncated
+    } else {
+        PathStatus::Complete
+    };
 
-    Ok((start, len))
+    Ok(ResolvedPath {
+        buf: base.add(resolver_context.buf_remainder),
+        len: MAX_BUF_LEN - resolver_context.buf_remainder,
+        status,
+        mnt_ns,
+    })
 }
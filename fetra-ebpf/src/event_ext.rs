@@ -1,34 +1,234 @@
-use crate::bindings::{file, super_block};
-use crate::d_path::d_path_local;
+use crate::bindings::{
+    dentry, file, inode, kiocb, ovl_entry, ovl_inode, path, super_block, task_struct,
+};
+use crate::container_of_mut;
+use crate::d_path::{d_path_from, d_path_local, dentry_path, PathRoot, ResolvedPath};
+use crate::helpers::Scratch;
+use aya_ebpf::helpers::{
+    bpf_get_current_cgroup_id, bpf_get_current_comm, bpf_get_current_task_btf,
+    bpf_probe_read_kernel,
+};
 use core::ffi::c_void;
-use core::ptr::copy_nonoverlapping;
-use fetra_common::FileAccessEvent;
+use core::mem::offset_of;
+use fetra_common::{FileAccessEvent, EVENT_FLAG_DIRECT};
 
+const O_DIRECT: u32 = 0o40000;
+const IOCB_DIRECT: i32 = 1 << 17;
+
+const OVERLAYFS_SUPER_MAGIC: u64 = 0x794c7630;
+
+/// Fills in the upper or lower file behind an overlayfs inode, which is what the page cache
+/// and block layer see, and which layer it lives in.
+unsafe fn populate_real(event: &mut FileAccessEvent, inode: *const inode) -> Result<(), i64> {
+    if event.s_magic != OVERLAYFS_SUPER_MAGIC {
+        return Ok(());
+    }
+
+    let ovl: *const ovl_inode = container_of_mut!(inode as *mut inode, ovl_inode, vfs_inode);
+    let upper = bpf_probe_read_kernel(&(*ovl).__upperdentry)?;
+    let (real_dentry, layer_idx) = if !upper.is_null() {
+        (upper, 0)
+    } else {
+        let oe: *mut ovl_entry = bpf_probe_read_kernel(&(*ovl).oe)?;
+        if oe.is_null() || bpf_probe_read_kernel(&(*oe).__numlower)? == 0 {
+            return Ok(());
+        }
+        let lower = bpf_probe_read_kernel((*oe).__lowerstack.as_ptr())?;
+        (lower.dentry, bpf_probe_read_kernel(&(*lower.layer).idx)?)
+    };
+
+    let real_inode: *const inode = bpf_probe_read_kernel(&(*real_dentry).d_inode)?;
+    if real_inode.is_null() {
+        return Ok(());
+    }
+    let sb_ptr: *const super_block = bpf_probe_read_kernel(&(*real_inode).i_sb)?;
+    event.real_inode = bpf_probe_read_kernel(&(*real_inode).i_ino)?;
+    event.real_dev = bpf_probe_read_kernel(&(*sb_ptr).s_dev)?;
+    event.real_s_magic = bpf_probe_read_kernel(&(*sb_ptr).s_magic)?;
+    event.ovl_layer = layer_idx as u8 + 1;
+    Ok(())
+}
+
+/// Fills in the file an event is about and resolves its path for the record.
 pub trait EventExt {
-    unsafe fn populate_from_file(&mut self, file: *const file, ctx: *mut c_void)
-        -> Result<(), i64>;
+    /// Fills in the task the event is attributed to.
+    unsafe fn populate_from_current(&mut self, tgid: u32, tid: u32) -> Result<(), i64>;
+
+    unsafe fn populate_from_file(
+        &mut self,
+        file: *const file,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64>;
+
+    /// Flags direct I/O from the kiocb, which kernel users such as nfsd mark `IOCB_DIRECT`
+    /// without opening the file `O_DIRECT`.
+    unsafe fn populate_kiocb_flags(&mut self, iocb: *const kiocb) -> Result<(), i64>;
+
+    unsafe fn populate_from_kiocb(
+        &mut self,
+        iocb: *const kiocb,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64>;
+
+    unsafe fn populate_from_path(
+        &mut self,
+        path: path,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64>;
+
+    /// Like [`Self::populate_from_path`], resolving the path relative to `root` in the `scratch`
+    /// slot.
+    unsafe fn populate_from_path_at(
+        &mut self,
+        path: path,
+        root: &PathRoot,
+        scratch: Scratch,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64>;
+
+    unsafe fn populate_from_dentry(
+        &mut self,
+        dentry: *const dentry,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64>;
+
+    /// Resolves the path relative to `root` in the `scratch` slot, inodes are looked at outside
+    /// of the context of the task that accessed them.
+    unsafe fn populate_from_inode(
+        &mut self,
+        inode: *const inode,
+        root: &PathRoot,
+        scratch: Scratch,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64>;
 }
 
 impl EventExt for FileAccessEvent {
+    unsafe fn populate_from_current(&mut self, tgid: u32, tid: u32) -> Result<(), i64> {
+        self.tid = tid;
+        self.tgid = tgid;
+        self.comm = bpf_get_current_comm()?;
+
+        // effective ids, the ones permission checks use, `bpf_get_current_uid_gid` returns the
+        // real ones
+        let task = bpf_get_current_task_btf() as *const task_struct;
+        let cred = (*task).cred;
+        self.uid = (*cred).euid.val;
+        self.gid = (*cred).egid.val;
+
+        self.ppid = (*(*task).real_parent).tgid as u32;
+        self.start_time = (*(*task).group_leader).start_boottime;
+        self.cgroup_id = bpf_get_current_cgroup_id();
+        Ok(())
+    }
+
     unsafe fn populate_from_file(
         &mut self,
         file: *const file,
         ctx: *mut c_void,
-    ) -> Result<(), i64> {
-        let inode_ptr = (*file).f_inode;
-        let sb_ptr: *const super_block = (*inode_ptr).i_sb;
-        let dev = (*sb_ptr).s_dev;
+    ) -> Result<ResolvedPath, i64> {
+        // probe reads throughout, `file` may have been read out of another struct rather than
+        // be a BTF typed argument the verifier lets us dereference
+        let inode_ptr: *const inode = bpf_probe_read_kernel(&(*file).f_inode)?;
+        let sb_ptr: *const super_block = bpf_probe_read_kernel(&(*inode_ptr).i_sb)?;
 
-        self.dev = dev;
-        self.inode = (*inode_ptr).i_ino;
-        self.s_magic = (*sb_ptr).s_magic;
-        self.i_mode = (*inode_ptr).i_mode;
+        self.dev = bpf_probe_read_kernel(&(*sb_ptr).s_dev)?;
+        self.inode = bpf_probe_read_kernel(&(*inode_ptr).i_ino)?;
+        self.s_magic = bpf_probe_read_kernel(&(*sb_ptr).s_magic)?;
+        self.i_mode = bpf_probe_read_kernel(&(*inode_ptr).i_mode)?;
+        self.i_uid = bpf_probe_read_kernel(&(*inode_ptr).i_uid.val)?;
+        self.i_gid = bpf_probe_read_kernel(&(*inode_ptr).i_gid.val)?;
 
-        let path = (*file).f_path;
+        if bpf_probe_read_kernel(&(*file).f_flags)? & O_DIRECT != 0 {
+            self.flags |= EVENT_FLAG_DIRECT;
+        }
 
-        let (buf, len) = d_path_local(ctx, path)?;
-        copy_nonoverlapping(buf, &mut self.path as *mut _, len.min(self.path.len()));
+        populate_real(self, inode_ptr)?;
 
+        Ok(d_path_local(ctx, bpf_probe_read_kernel(&(*file).f_path)?))
+    }
+
+    unsafe fn populate_kiocb_flags(&mut self, iocb: *const kiocb) -> Result<(), i64> {
+        let ki_flags = bpf_probe_read_kernel(&(*iocb).ki_flags)?;
+        if ki_flags & IOCB_DIRECT != 0 {
+            self.flags |= EVENT_FLAG_DIRECT;
+        }
         Ok(())
     }
+
+    unsafe fn populate_from_kiocb(
+        &mut self,
+        iocb: *const kiocb,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64> {
+        self.populate_kiocb_flags(iocb)?;
+
+        let file = bpf_probe_read_kernel(&(*iocb).ki_filp)?;
+        self.populate_from_file(file, ctx)
+    }
+
+    unsafe fn populate_from_path(
+        &mut self,
+        path: path,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64> {
+        self.populate_from_path_at(path, &PathRoot::current()?, Scratch::Task, ctx)
+    }
+
+    unsafe fn populate_from_path_at(
+        &mut self,
+        path: path,
+        root: &PathRoot,
+        scratch: Scratch,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64> {
+        let sb_ptr: *const super_block = bpf_probe_read_kernel(&(*path.dentry).d_sb)?;
+        self.dev = bpf_probe_read_kernel(&(*sb_ptr).s_dev)?;
+        self.s_magic = bpf_probe_read_kernel(&(*sb_ptr).s_magic)?;
+
+        // negative dentry, e.g. the target of mkdir
+        let inode_ptr: *const inode = bpf_probe_read_kernel(&(*path.dentry).d_inode)?;
+        if !inode_ptr.is_null() {
+            self.inode = bpf_probe_read_kernel(&(*inode_ptr).i_ino)?;
+            self.i_mode = bpf_probe_read_kernel(&(*inode_ptr).i_mode)?;
+            self.i_uid = bpf_probe_read_kernel(&(*inode_ptr).i_uid.val)?;
+            self.i_gid = bpf_probe_read_kernel(&(*inode_ptr).i_gid.val)?;
+            populate_real(self, inode_ptr)?;
+        }
+
+        Ok(d_path_from(ctx, path, root, scratch))
+    }
+
+    unsafe fn populate_from_dentry(
+        &mut self,
+        dentry: *const dentry,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64> {
+        self.populate_from_path(dentry_path(dentry)?, ctx)
+    }
+
+    unsafe fn populate_from_inode(
+        &mut self,
+        inode: *const inode,
+        root: &PathRoot,
+        scratch: Scratch,
+        ctx: *mut c_void,
+    ) -> Result<ResolvedPath, i64> {
+        let first = bpf_probe_read_kernel(&(*inode).__bindgen_anon_2.i_dentry.first)?;
+        if !first.is_null() {
+            // any alias will do, hard links share the inode
+            let dentry: *mut dentry = container_of_mut!(first, dentry, d_u);
+            return self.populate_from_path_at(dentry_path(dentry)?, root, scratch, ctx);
+        }
+
+        let sb_ptr: *const super_block = bpf_probe_read_kernel(&(*inode).i_sb)?;
+        self.dev = bpf_probe_read_kernel(&(*sb_ptr).s_dev)?;
+        self.s_magic = bpf_probe_read_kernel(&(*sb_ptr).s_magic)?;
+        self.inode = bpf_probe_read_kernel(&(*inode).i_ino)?;
+        self.i_mode = bpf_probe_read_kernel(&(*inode).i_mode)?;
+        self.i_uid = bpf_probe_read_kernel(&(*inode).i_uid.val)?;
+        self.i_gid = bpf_probe_read_kernel(&(*inode).i_gid.val)?;
+        populate_real(self, inode)?;
+        Ok(ResolvedPath::empty())
+    }
 }
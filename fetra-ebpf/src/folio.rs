@@ -0,0 +1,46 @@
+use crate::bindings::folio;
+use crate::bindings::inode;
+use crate::bindings::page;
+use crate::bindings::pageflags::PG_head;
+use aya_ebpf::helpers::bpf_probe_read_kernel;
+
+const PG_HEAD_MASK: u64 = 1 << PG_head as u64;
+const PAGE_MAPPING_ANON: u64 = 0x1;
+
+/// Number of base pages in the folio.
+#[inline(always)]
+pub unsafe fn folio_nr_pages(folio: *const folio) -> Result<u64, i64> {
+    let flags = bpf_probe_read_kernel(&(*folio).__bindgen_anon_1.__bindgen_anon_1.flags)?;
+    if flags & PG_HEAD_MASK == 0 {
+        return Ok(1);
+    }
+
+    let flags_1 = bpf_probe_read_kernel(&(*folio).__bindgen_anon_2.__bindgen_anon_1._flags_1)?;
+    Ok(1u64 << (flags_1 & 0xff))
+}
+
+/// Inode owning a page cache folio, `None` for anonymous and unmapped folios.
+#[inline(always)]
+pub unsafe fn folio_inode(folio: *const folio) -> Result<Option<*mut inode>, i64> {
+    let mapping = bpf_probe_read_kernel(&(*folio).__bindgen_anon_1.__bindgen_anon_1.mapping)?;
+    if mapping.is_null() || mapping as u64 & PAGE_MAPPING_ANON != 0 {
+        return Ok(None);
+    }
+
+    let host = bpf_probe_read_kernel(&(*mapping).host)?;
+    if host.is_null() {
+        return Ok(None);
+    }
+
+    Ok(Some(host))
+}
+
+/// Folio containing the page, resolving tail pages to their head.
+#[inline(always)]
+pub unsafe fn page_folio(page: *const page) -> Result<*const folio, i64> {
+    let head = bpf_probe_read_kernel(&(*page).__bindgen_anon_1.__bindgen_anon_3.compound_head)?;
+    if head & 1 != 0 {
+        return Ok((head - 1) as *const folio);
+    }
+    Ok(page as *const folio)
+}
@@ -0,0 +1,53 @@
+use crate::bindings::{file, iocb, iovec, kiocb};
+use crate::event_ext::EventExt;
+use crate::handler::{output_event, total_iovec_len};
+use crate::helpers::filter_tgids;
+use aya_ebpf::helpers::bpf_probe_read_kernel;
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::EbpfContext;
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent};
+
+pub(crate) unsafe fn try_handle_aio_read(ctx: &FEntryContext) -> Result<(), i64> {
+    handle_aio_rw(ctx, EventType::AioRead)
+}
+
+pub(crate) unsafe fn try_handle_aio_write(ctx: &FEntryContext) -> Result<(), i64> {
+    handle_aio_rw(ctx, EventType::AioWrite)
+}
+
+/// Counts reads and writes submitted through `io_submit`, direct or buffered, on any filesystem
+/// or raw block device. They go straight to `->read_iter`/`->write_iter`, past the vfs probes.
+#[inline(always)]
+unsafe fn handle_aio_rw(ctx: &FEntryContext, event_type: EventType) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let req: *const kiocb = ctx.arg(0);
+    let iocb: *const iocb = ctx.arg(1);
+    let vectored: u8 = ctx.arg(2);
+    let compat: u8 = ctx.arg(3);
+
+    // `__io_submit_one` already took the file reference, the rest of the kiocb is only set up
+    // by `aio_prep_rw`
+    let file: *const file = bpf_probe_read_kernel(&(*req).ki_filp)?;
+    let buf = bpf_probe_read_kernel(&(*iocb).aio_buf)?;
+    let nbytes = bpf_probe_read_kernel(&(*iocb).aio_nbytes)?;
+    let bytes = match (vectored, compat) {
+        (0, _) => nbytes,
+        (_, 0) => total_iovec_len(buf as *const iovec, nbytes as usize)?,
+        // 32-bit iovecs are laid out differently
+        _ => 0,
+    };
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = event_type;
+    event.populate_from_current(tgid, tid)?;
+    event.bytes = bytes;
+
+    let path = event.populate_from_file(file, ctx.as_ptr())?;
+
+    output_event(&event, &path)?;
+    Ok(())
+}
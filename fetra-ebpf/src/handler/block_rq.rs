@@ -0,0 +1,152 @@
+use crate::bindings::{bio, block_device, request};
+use crate::d_path::{PathRoot, ResolvedPath};
+use crate::event_ext::EventExt;
+use crate::folio::{folio_inode, page_folio};
+use crate::handler::output_event_in;
+use crate::helpers::{filter_tgids, Scratch, BPF_NOEXIST};
+use aya_ebpf::helpers::{bpf_ktime_get_ns, bpf_probe_read_kernel};
+use aya_ebpf::programs::BtfTracePointContext;
+use aya_ebpf::EbpfContext;
+use aya_ebpf::{macros::map, maps::LruHashMap};
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent, EVENT_FLAG_RQ_ERROR};
+
+const REQ_OP_MASK: u32 = 0xff;
+const REQ_OP_READ: u32 = 0;
+const REQ_OP_WRITE: u32 = 1;
+
+/// A request between being queued and its completion.
+#[repr(C)]
+struct QueuedRq {
+    /// Attributed to the task that queued the request.
+    event: FileAccessEvent,
+    /// Root of that task, completions run in interrupt context on behalf of no task at all.
+    root: PathRoot,
+    inserted_ns: u64,
+    issued_ns: u64,
+}
+
+/// Requests in flight keyed by `request` address.
+#[map(name = "BLOCK_RQS")]
+static mut BLOCK_RQS: LruHashMap<u64, QueuedRq> = LruHashMap::with_max_entries(10240, 0);
+
+/// Starts tracking `rq` on behalf of the current task. `dev` is the block device the request
+/// goes to, which differs from the `s_dev` of the file for stacked devices and filesystems with
+/// anonymous devices such as btrfs.
+#[inline(always)]
+unsafe fn queue_rq(rq: *const request, now: u64, issued_ns: u64) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let cmd_flags = bpf_probe_read_kernel(&(*rq).cmd_flags)?;
+    let event_type = match cmd_flags & REQ_OP_MASK {
+        REQ_OP_READ => EventType::BlockRqRead,
+        REQ_OP_WRITE => EventType::BlockRqWrite,
+        _ => return Ok(()),
+    };
+
+    let part: *const block_device = bpf_probe_read_kernel(&(*rq).part)?;
+    if part.is_null() {
+        return Ok(());
+    }
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = event_type;
+    event.populate_from_current(tgid, tid)?;
+    event.dev = bpf_probe_read_kernel(&(*part).bd_dev)?;
+
+    let queued = QueuedRq {
+        event,
+        root: PathRoot::current()?,
+        inserted_ns: now,
+        issued_ns,
+    };
+    // requeued requests are inserted again, they keep their first insertion
+    let _ = BLOCK_RQS.insert(&(rq as u64), &queued, BPF_NOEXIST);
+    Ok(())
+}
+
+/// Runs in the task submitting the bio, or the one flushing its plug, before the request waits
+/// in the I/O scheduler or the dispatch queue.
+pub(crate) unsafe fn try_handle_block_rq_insert(ctx: &BtfTracePointContext) -> Result<(), i64> {
+    let rq: *const request = ctx.arg(0);
+    queue_rq(rq, bpf_ktime_get_ns(), 0)
+}
+
+/// Requests issued straight to the driver skip the insertion, the submitting task issues them.
+pub(crate) unsafe fn try_handle_block_rq_issue(ctx: &BtfTracePointContext) -> Result<(), i64> {
+    let rq: *const request = ctx.arg(0);
+    let now = bpf_ktime_get_ns();
+    match BLOCK_RQS.get_ptr_mut(&(rq as u64)) {
+        Some(queued) => (*queued).issued_ns = now,
+        None => queue_rq(rq, now, now)?,
+    }
+    Ok(())
+}
+
+/// The file is taken from the page cache mapping of the first page, so only buffered I/O gets a
+/// path. Direct I/O into user memory, metadata and swap requests are counted for the device only.
+#[inline(always)]
+unsafe fn rq_path(
+    rq: *const request,
+    event: &mut FileAccessEvent,
+    root: &PathRoot,
+    ctx: &BtfTracePointContext,
+) -> Result<ResolvedPath, i64> {
+    let bio: *const bio = bpf_probe_read_kernel(&(*rq).bio)?;
+    if bio.is_null() {
+        return Ok(ResolvedPath::empty());
+    }
+
+    let bvec = bpf_probe_read_kernel(&(*bio).bi_io_vec)?;
+    let page = bpf_probe_read_kernel(&(*bvec).bv_page)?;
+    if page.is_null() {
+        return Ok(ResolvedPath::empty());
+    }
+
+    let Some(inode) = folio_inode(page_folio(page)?)? else {
+        return Ok(ResolvedPath::empty());
+    };
+
+    // keeps the device of the request
+    let dev = event.dev;
+    let path = event.populate_from_inode(inode, root, Scratch::Irq, ctx.as_ptr());
+    event.dev = dev;
+    path
+}
+
+pub(crate) unsafe fn try_handle_block_rq_complete(ctx: &BtfTracePointContext) -> Result<(), i64> {
+    let rq: *const request = ctx.arg(0);
+    let error: u8 = ctx.arg(1);
+    let nr_bytes: u32 = ctx.arg(2);
+
+    let key = rq as u64;
+    let Some(queued) = BLOCK_RQS.get(&key) else {
+        return Ok(());
+    };
+    let mut event = queued.event;
+    let root = queued.root;
+    let inserted_ns = queued.inserted_ns;
+    let issued_ns = queued.issued_ns;
+    BLOCK_RQS.remove(&key)?;
+
+    let now = bpf_ktime_get_ns();
+    if error != 0 {
+        event.flags |= EVENT_FLAG_RQ_ERROR;
+    }
+    event.bytes = nr_bytes as u64;
+    if issued_ns == 0 {
+        // failed before reaching the driver, it was queued until now
+        event.queue_ns = now.saturating_sub(inserted_ns);
+    } else {
+        event.queue_ns = issued_ns.saturating_sub(inserted_ns);
+        event.duration_ns = now.saturating_sub(issued_ns);
+    }
+
+    // the bios are only ended after this tracepoint, so the pages are still attached; the path
+    // is best effort, the request is still accounted to the device. Completions run in
+    // interrupt context, hence their own scratch slot.
+    let path = rq_path(rq, &mut event, &root, ctx).unwrap_or(ResolvedPath::empty());
+    output_event_in(&event, &path, Scratch::Irq)
+}
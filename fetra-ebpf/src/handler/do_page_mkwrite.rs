@@ -0,0 +1,35 @@
+use crate::bindings::{folio, vm_fault};
+use crate::event_ext::EventExt;
+use crate::folio::folio_nr_pages;
+use crate::handler::filemap_fault::{fault_flags, populate_from_vmf};
+use crate::handler::output_event;
+use crate::helpers::filter_tgids;
+use crate::PAGE_SIZE;
+use aya_ebpf::programs::FExitContext;
+use aya_ebpf::EbpfContext;
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent, EVENT_FLAG_FAULT_MKWRITE};
+
+/// First write to a clean page of a shared file mapping, the page is mapped already so
+/// `filemap_fault` doesn't see it. Hooked here rather than at `filemap_page_mkwrite` because most
+/// block filesystems (ext4, xfs, btrfs) bring their own `page_mkwrite`.
+pub(crate) unsafe fn try_handle_do_page_mkwrite_exit(ctx: &FExitContext) -> Result<(), i64> {
+    let vmf: *const vm_fault = ctx.arg(0);
+    let folio: *const folio = ctx.arg(1);
+    let ret: u32 = ctx.arg(2);
+
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = EventType::MmapWrite;
+    event.populate_from_current(tgid, tid)?;
+    event.bytes = folio_nr_pages(folio)? * PAGE_SIZE;
+    event.flags = EVENT_FLAG_FAULT_MKWRITE | fault_flags(ret);
+
+    let path = populate_from_vmf(&mut event, vmf, ctx.as_ptr())?;
+
+    output_event(&event, &path)?;
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use crate::bindings::dentry;
+use crate::event_ext::EventExt;
+use crate::handler::output_meta_event;
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::EbpfContext;
+use fetra_common::EventType;
+
+pub(crate) unsafe fn try_handle_do_truncate(ctx: &FEntryContext) -> Result<(), i64> {
+    let dentry: *const dentry = ctx.arg(1);
+    let length: i64 = ctx.arg(2);
+
+    output_meta_event(EventType::DoTruncate, |record| {
+        let meta = &mut *record.header();
+        meta.event.bytes = length as u64;
+        let path = meta.event.populate_from_dentry(dentry, ctx.as_ptr())?;
+        record.push_path(&path)
+    })
+}
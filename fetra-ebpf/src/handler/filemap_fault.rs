@@ -1,17 +1,36 @@
 use crate::bindings::fault_flag::{FAULT_FLAG_MKWRITE, FAULT_FLAG_WRITE};
-use crate::bindings::pageflags::PG_head;
 use crate::bindings::{file, page, super_block, vm_area_struct, vm_fault};
-use crate::d_path::d_path_local;
+use crate::d_path::{d_path_local, ResolvedPath};
+use crate::event_ext::EventExt;
+use crate::folio::{folio_nr_pages, page_folio};
+use crate::handler::output_event;
 use crate::helpers::filter_tgids;
-use crate::{EVENTS, PAGE_SIZE};
-use aya_ebpf::helpers::{bpf_get_current_comm, bpf_probe_read_kernel};
+use crate::PAGE_SIZE;
+use aya_ebpf::helpers::bpf_probe_read_kernel;
 use aya_ebpf::programs::FExitContext;
 use aya_ebpf::EbpfContext;
 use bytemuck::Zeroable;
-use core::ptr::copy_nonoverlapping;
-use fetra_common::{EventType, FileAccessEvent};
-
-const PG_HEAD_MASK: u64 = 1 << PG_head as u64;
+use core::ffi::c_void;
+use fetra_common::{
+    EventType, FileAccessEvent, EVENT_FLAG_FAULT_ERROR, EVENT_FLAG_FAULT_MAJOR,
+    EVENT_FLAG_FAULT_RETRY, EVENT_FLAG_FAULT_WRITE,
+};
+
+const VM_FAULT_OOM: u32 = 0x000001;
+const VM_FAULT_SIGBUS: u32 = 0x000002;
+const VM_FAULT_MAJOR: u32 = 0x000004;
+const VM_FAULT_HWPOISON: u32 = 0x000010;
+const VM_FAULT_HWPOISON_LARGE: u32 = 0x000020;
+const VM_FAULT_SIGSEGV: u32 = 0x000040;
+const VM_FAULT_NOPAGE: u32 = 0x000100;
+const VM_FAULT_RETRY: u32 = 0x000400;
+const VM_FAULT_FALLBACK: u32 = 0x000800;
+const VM_FAULT_ERROR: u32 = VM_FAULT_OOM
+    | VM_FAULT_SIGBUS
+    | VM_FAULT_SIGSEGV
+    | VM_FAULT_HWPOISON
+    | VM_FAULT_HWPOISON_LARGE
+    | VM_FAULT_FALLBACK;
 
 unsafe fn vmf_file(vmf: *const vm_fault) -> Result<*const file, i64> {
     let vma: *const vm_area_struct = bpf_probe_read_kernel(&(*vmf).__bindgen_anon_1.vma)?;
@@ -23,47 +42,65 @@ unsafe fn is_write(flags: u64) -> bool {
     flags & (FAULT_FLAG_WRITE | FAULT_FLAG_MKWRITE) as u64 != 0
 }
 
-unsafe fn bytes_from_page(vmf: *const vm_fault) -> Result<(EventType, u64), i64> {
-    let fault_flags = bpf_probe_read_kernel(&(*vmf).flags)?;
-    let event_type = if fault_flags & (FAULT_FLAG_WRITE | FAULT_FLAG_MKWRITE) != 0 {
+/// `filemap_fault` hands back the faulting page for `finish_fault`, which maps the whole large
+/// folio around it at once when it fits in the VMA; other neighbours are mapped by fault-around
+/// in `filemap_map_pages`. On folio kernels the page is only set on success, so a missing page
+/// means the fault failed or has to be retried, unless the handler installed the PTE itself
+/// (`VM_FAULT_NOPAGE`).
+unsafe fn bytes_from_page(
+    vmf: *const vm_fault,
+    ret: u32,
+    write: bool,
+) -> Result<(EventType, u64), i64> {
+    let event_type = if write {
         EventType::MmapWrite
     } else {
         EventType::MmapRead
     };
 
-    let page_ptr: *const page = bpf_probe_read_kernel(&(*vmf).page)?;
-    if page_ptr.is_null() {
+    if ret & VM_FAULT_ERROR != 0 {
         return Ok((EventType::NullPage, 0));
     }
 
-    let mut num_pages = 1u64;
+    // counted once the retried fault succeeds
+    if ret & VM_FAULT_RETRY != 0 {
+        return Ok((event_type, 0));
+    }
 
-    let pgflags: u64 = bpf_probe_read_kernel(&(*page_ptr).flags)?;
-    if pgflags & PG_HEAD_MASK != 0 {
-        let order_ptr = page_ptr.add(1) as *const u8;
-        let order: u8 = bpf_probe_read_kernel(order_ptr)?;
-        num_pages = 1u64 << order as u64;
+    let page_ptr: *const page = bpf_probe_read_kernel(&(*vmf).page)?;
+    if page_ptr.is_null() {
+        if ret & VM_FAULT_NOPAGE == 0 {
+            return Ok((EventType::NullPage, 0));
+        }
+        return Ok((event_type, PAGE_SIZE));
     }
 
-    Ok((event_type, num_pages * PAGE_SIZE))
+    Ok((
+        event_type,
+        folio_nr_pages(page_folio(page_ptr)?)? * PAGE_SIZE,
+    ))
 }
 
-pub unsafe fn try_handle_filemap_fault(ctx: &FExitContext) -> Result<(), i64> {
-    let vmf: *const vm_fault = ctx.arg(0);
-
-    let Some((tgid, tid)) = filter_tgids() else {
-        return Ok(());
-    };
-
-    let (event_type, bytes) = bytes_from_page(vmf)?;
-
-    let mut event = FileAccessEvent::zeroed();
-    event.event_type = event_type;
-    event.tid = tid;
-    event.tgid = tgid;
-    event.comm = bpf_get_current_comm()?;
-    event.bytes = bytes;
+/// `EVENT_FLAG_FAULT_*` bits for a `vm_fault_t` return value, no bits means a minor fault.
+#[inline(always)]
+pub(crate) fn fault_flags(ret: u32) -> u16 {
+    if ret & VM_FAULT_ERROR != 0 {
+        EVENT_FLAG_FAULT_ERROR
+    } else if ret & VM_FAULT_RETRY != 0 {
+        EVENT_FLAG_FAULT_RETRY
+    } else if ret & VM_FAULT_MAJOR != 0 {
+        EVENT_FLAG_FAULT_MAJOR
+    } else {
+        0
+    }
+}
 
+/// Fills in the file the fault happened in.
+pub(crate) unsafe fn populate_from_vmf(
+    event: &mut FileAccessEvent,
+    vmf: *const vm_fault,
+    ctx: *mut c_void,
+) -> Result<ResolvedPath, i64> {
     let f = vmf_file(vmf)?;
 
     // TODO: why doesn't it work and in this case opposed to others,
@@ -77,12 +114,37 @@ pub unsafe fn try_handle_filemap_fault(ctx: &FExitContext) -> Result<(), i64> {
     event.inode = bpf_probe_read_kernel(&(*inode_ptr).i_ino)?;
     event.s_magic = bpf_probe_read_kernel(&(*sb_ptr).s_magic)?;
     event.i_mode = bpf_probe_read_kernel(&(*inode_ptr).i_mode)?;
+    event.i_uid = bpf_probe_read_kernel(&(*inode_ptr).i_uid.val)?;
+    event.i_gid = bpf_probe_read_kernel(&(*inode_ptr).i_gid.val)?;
 
     let path = bpf_probe_read_kernel(&(*f).f_path)?;
 
-    let (buf, len) = d_path_local(ctx.as_ptr(), path)?;
-    copy_nonoverlapping(buf, &mut event.path as *mut _, len.min(event.path.len()));
+    Ok(d_path_local(ctx, path))
+}
+
+pub unsafe fn try_handle_filemap_fault(ctx: &FExitContext) -> Result<(), i64> {
+    let vmf: *const vm_fault = ctx.arg(0);
+    let ret: u32 = ctx.arg(1);
+
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let write = is_write(bpf_probe_read_kernel(&(*vmf).flags)? as u64);
+    let (event_type, bytes) = bytes_from_page(vmf, ret, write)?;
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = event_type;
+    event.populate_from_current(tgid, tid)?;
+    event.bytes = bytes;
+    event.flags = fault_flags(ret);
+    // NullPage events don't tell reads from writes by their type
+    if write {
+        event.flags |= EVENT_FLAG_FAULT_WRITE;
+    }
+
+    let path = populate_from_vmf(&mut event, vmf, ctx.as_ptr())?;
 
-    EVENTS.output(&event, 0)?;
+    output_event(&event, &path)?;
     Ok(())
 }
@@ -0,0 +1,78 @@
+use crate::bindings::vm_fault;
+use crate::event_ext::EventExt;
+use crate::handler::filemap_fault::populate_from_vmf;
+use crate::handler::output_event;
+use crate::helpers::filter_tgids;
+use crate::PAGE_SIZE;
+use aya_ebpf::helpers::bpf_get_current_pid_tgid;
+use aya_ebpf::programs::{FEntryContext, FExitContext};
+use aya_ebpf::EbpfContext;
+use aya_ebpf::{macros::map, maps::HashMap};
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent};
+
+/// Pages mapped so far by the `filemap_map_pages` call running on the thread.
+///
+/// `filemap_map_pages` doesn't report how much it mapped, so the `set_pte_range` (or
+/// `do_set_pte`) calls it makes are summed up in between entry and exit.
+#[map(name = "MAPPED_PAGES")]
+static mut MAPPED_PAGES: HashMap<u32, u64> = HashMap::with_max_entries(10240, 0);
+
+pub(crate) unsafe fn try_handle_filemap_map_pages(_ctx: &FEntryContext) -> Result<(), i64> {
+    let Some((_tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    MAPPED_PAGES.insert(&tid, &0, 0)
+}
+
+pub(crate) unsafe fn try_handle_set_pte_range(ctx: &FEntryContext) -> Result<(), i64> {
+    let nr: u32 = ctx.arg(3);
+    add_mapped_pages(nr as u64);
+    Ok(())
+}
+
+/// `do_set_pte` maps a single page and is what `set_pte_range` replaced in 6.6.
+pub(crate) unsafe fn try_handle_do_set_pte(_ctx: &FEntryContext) -> Result<(), i64> {
+    add_mapped_pages(1);
+    Ok(())
+}
+
+#[inline(always)]
+unsafe fn add_mapped_pages(nr: u64) {
+    let tid = bpf_get_current_pid_tgid() as u32;
+    // finish_fault, already accounted for by filemap_fault
+    let Some(mapped) = MAPPED_PAGES.get_ptr_mut(&tid) else {
+        return;
+    };
+
+    *mapped += nr;
+}
+
+/// Fault-around only runs for read faults and only maps pages that are already uptodate in the
+/// page cache, so these are always minor `MmapRead` faults.
+pub(crate) unsafe fn try_handle_filemap_map_pages_exit(ctx: &FExitContext) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let Some(&mapped) = MAPPED_PAGES.get(&tid) else {
+        return Ok(());
+    };
+    let _ = MAPPED_PAGES.remove(&tid);
+    if mapped == 0 {
+        return Ok(());
+    }
+
+    let vmf: *const vm_fault = ctx.arg(0);
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = EventType::MmapRead;
+    event.populate_from_current(tgid, tid)?;
+    event.bytes = mapped * PAGE_SIZE;
+
+    let path = populate_from_vmf(&mut event, vmf, ctx.as_ptr())?;
+
+    output_event(&event, &path)?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use crate::bindings::{file, io_kiocb, io_uring_cqe};
+use crate::event_ext::EventExt;
+use crate::handler::output_event;
+use crate::helpers::filter_tgids;
+use aya_ebpf::helpers::{bpf_ktime_get_ns, bpf_probe_read_kernel};
+use aya_ebpf::programs::BtfTracePointContext;
+use aya_ebpf::EbpfContext;
+use aya_ebpf::{macros::map, maps::LruHashMap};
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent};
+
+const IORING_OP_READV: u8 = 1;
+const IORING_OP_WRITEV: u8 = 2;
+const IORING_OP_READ_FIXED: u8 = 4;
+const IORING_OP_WRITE_FIXED: u8 = 5;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+
+/// Events of in-flight requests keyed by `io_kiocb` address.
+///
+/// Issue and completion may run on io-wq workers or in task work of another task, so
+/// everything that identifies the submitter is captured at submission time. The file is only
+/// known once the request was issued and is resolved on completion.
+#[map(name = "IO_URING_REQS")]
+static mut IO_URING_REQS: LruHashMap<u64, FileAccessEvent> = LruHashMap::with_max_entries(10240, 0);
+
+pub(crate) unsafe fn try_handle_io_uring_submit_req(ctx: &BtfTracePointContext) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let req: *const io_kiocb = ctx.arg(0);
+    let opcode = bpf_probe_read_kernel(&(*req).opcode)?;
+    let event_type = match opcode {
+        IORING_OP_READV | IORING_OP_READ_FIXED | IORING_OP_READ => EventType::IoUringRead,
+        IORING_OP_WRITEV | IORING_OP_WRITE_FIXED | IORING_OP_WRITE => EventType::IoUringWrite,
+        _ => return Ok(()),
+    };
+
+    // `req->file` is only assigned when the request is issued, which may be long after
+    // submission on an io-wq worker, so the file is looked at on completion
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = event_type;
+    event.populate_from_current(tgid, tid)?;
+    // submission timestamp until the request completes
+    event.duration_ns = bpf_ktime_get_ns();
+
+    IO_URING_REQS.insert(&(req as u64), &event, 0)?;
+    Ok(())
+}
+
+pub(crate) unsafe fn try_handle_io_uring_complete(ctx: &BtfTracePointContext) -> Result<(), i64> {
+    let req: *const io_kiocb = ctx.arg(1);
+    let cqe: *const io_uring_cqe = ctx.arg(2);
+
+    let key = req as u64;
+    let Some(&submitted) = IO_URING_REQS.get(&key) else {
+        return Ok(());
+    };
+    IO_URING_REQS.remove(&key)?;
+
+    let res = bpf_probe_read_kernel(&(*cqe).res)?;
+    if res < 0 {
+        return Ok(());
+    }
+
+    // the request holds a file reference until it is freed after completion, requests that
+    // failed before being issued never got one
+    let file: *const file = bpf_probe_read_kernel(&(*req).__bindgen_anon_1.file)?;
+    if file.is_null() {
+        return Ok(());
+    }
+
+    let mut event = submitted;
+    event.bytes = res as u64;
+    event.duration_ns = bpf_ktime_get_ns().saturating_sub(submitted.duration_ns);
+
+    let path = event.populate_from_file(file, ctx.as_ptr())?;
+    output_event(&event, &path)
+}
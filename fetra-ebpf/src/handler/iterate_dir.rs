@@ -0,0 +1,30 @@
+use crate::bindings::file;
+use crate::event_ext::EventExt;
+use crate::handler::output_event;
+use crate::helpers::{filter_tgids, sample_metadata};
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::EbpfContext;
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent};
+
+pub(crate) unsafe fn try_handle_iterate_dir(ctx: &FEntryContext) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let Some(weight) = sample_metadata() else {
+        return Ok(());
+    };
+
+    let file: *const file = ctx.arg(0);
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = EventType::IterateDir;
+    event.populate_from_current(tgid, tid)?;
+    event.weight = weight;
+
+    let path = event.populate_from_file(file, ctx.as_ptr())?;
+
+    output_event(&event, &path)?;
+    Ok(())
+}
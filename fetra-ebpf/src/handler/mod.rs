@@ -1,14 +1,194 @@
-use crate::bindings::iovec;
-use aya_ebpf::helpers::bpf_probe_read_user;
+use crate::bindings::{file, inode, iovec, super_block};
+use crate::d_path::ResolvedPath;
+use crate::event_ext::EventExt;
+use crate::helpers::{filter_tgids, Scratch};
+use crate::record::Record;
+use crate::{EVENTS, META_EVENTS};
+use aya_ebpf::helpers::{bpf_ktime_get_ns, bpf_probe_read_kernel, bpf_probe_read_user};
+use aya_ebpf::{
+    macros::map,
+    maps::{HashMap, LruHashMap},
+};
+use fetra_common::{AccessPattern, EventType, FileAccessEvent, MetaOpEvent};
 
+pub(crate) mod aio;
+pub(crate) mod block_rq;
+pub(crate) mod do_page_mkwrite;
+pub(crate) mod do_truncate;
 pub(crate) mod filemap_fault;
+pub(crate) mod filemap_map_pages;
+pub(crate) mod io_uring;
+pub(crate) mod iterate_dir;
+pub(crate) mod nfs_file;
+pub(crate) mod page_cache;
+pub(crate) mod sync_file_range;
+pub(crate) mod vfs_fallocate;
+pub(crate) mod vfs_fsync_range;
+pub(crate) mod vfs_getattr;
+pub(crate) mod vfs_getxattr;
+pub(crate) mod vfs_iocb_iter;
+pub(crate) mod vfs_mkdir;
 pub(crate) mod vfs_read;
 pub(crate) mod vfs_readv;
+pub(crate) mod vfs_rename;
+pub(crate) mod vfs_rmdir;
+pub(crate) mod vfs_setxattr;
+pub(crate) mod vfs_unlink;
 pub(crate) mod vfs_write;
 pub(crate) mod vfs_writev;
+pub(crate) mod writeback;
 
 const MAX_IOVEC: usize = 31;
 
+/// A call in progress on a file. Keyed by file too, as stacked filesystems call back into
+/// the same function for the underlying file, e.g. overlayfs `ovl_fsync` into `vfs_fsync_range`.
+#[repr(C)]
+struct StartKey {
+    file: u64,
+    tid: u32,
+    _pad1: u32,
+}
+
+impl StartKey {
+    fn new(tid: u32, file: *const file) -> Self {
+        Self {
+            file: file as u64,
+            tid,
+            _pad1: 0,
+        }
+    }
+}
+
+#[map(name = "START_TS")]
+static mut START_TS: HashMap<StartKey, u64> = HashMap::with_max_entries(10240, 0);
+
+#[inline(always)]
+unsafe fn record_start(tid: u32, file: *const file) -> Result<(), i64> {
+    START_TS.insert(&StartKey::new(tid, file), &bpf_ktime_get_ns(), 0)
+}
+
+/// Returns the time elapsed since the matching [`record_start`] call on this thread and file.
+#[inline(always)]
+unsafe fn take_elapsed(tid: u32, file: *const file) -> Option<u64> {
+    let key = StartKey::new(tid, file);
+    let start = *START_TS.get(&key)?;
+    let _ = START_TS.remove(&key);
+    Some(bpf_ktime_get_ns().saturating_sub(start))
+}
+
+/// A file as accessed by one process, through any of its descriptors.
+#[repr(C)]
+struct FilePosKey {
+    inode: u64,
+    dev: u32,
+    tgid: u32,
+}
+
+/// Offset where the last read or write of a file by a process ended.
+#[map(name = "FILE_POS")]
+static mut FILE_POS: LruHashMap<FilePosKey, u64> = LruHashMap::with_max_entries(16384, 0);
+
+/// Classifies a read or write at `*pos` against where the previous one of the file by the same
+/// process ended. `pos` is null for streams such as pipes and sockets, which have no offset.
+#[inline(always)]
+unsafe fn track_access(event: &mut FileAccessEvent, pos: *const i64) -> Result<(), i64> {
+    if pos.is_null() {
+        return Ok(());
+    }
+
+    let pos = bpf_probe_read_kernel(pos)? as u64;
+    let key = FilePosKey {
+        inode: event.inode,
+        dev: event.dev,
+        tgid: event.tgid,
+    };
+    event.pos = pos;
+    event.pattern = match FILE_POS.get(&key) {
+        Some(&end) if end == pos => AccessPattern::Sequential,
+        Some(_) if pos == 0 => AccessPattern::Rewind,
+        Some(_) => AccessPattern::Random,
+        // the file was just opened, or by a process running since before fetra
+        None if pos == 0 => AccessPattern::Sequential,
+        None => AccessPattern::Random,
+    };
+    Ok(())
+}
+
+/// Records where a read or write of `file` that returned `ret` ended. Called on exit, when the
+/// kernel has advanced `*pos` by the bytes actually transferred rather than the requested ones,
+/// so that a short read followed by a read of the rest stays sequential.
+#[inline(always)]
+unsafe fn track_access_end(
+    tgid: u32,
+    file: *const file,
+    pos: *const i64,
+    ret: isize,
+) -> Result<(), i64> {
+    if pos.is_null() || ret <= 0 {
+        return Ok(());
+    }
+
+    let inode_ptr: *const inode = bpf_probe_read_kernel(&(*file).f_inode)?;
+    let sb_ptr: *const super_block = bpf_probe_read_kernel(&(*inode_ptr).i_sb)?;
+    let key = FilePosKey {
+        inode: bpf_probe_read_kernel(&(*inode_ptr).i_ino)?,
+        dev: bpf_probe_read_kernel(&(*sb_ptr).s_dev)?,
+        tgid,
+    };
+    let end = bpf_probe_read_kernel(pos)? as u64;
+    FILE_POS.insert(&key, &end, 0)
+}
+
+/// Length of the `[start, end]` flush range, clamped to the current file size.
+#[inline(always)]
+unsafe fn sync_range_len(file: *const file, start: i64, end: i64) -> u64 {
+    let size = (*(*file).f_inode).i_size;
+    let end = end.min(size - 1);
+    if end < start {
+        return 0;
+    }
+    (end - start + 1) as u64
+}
+
+/// Sends `event` followed by its path to `EVENTS`.
+#[inline(always)]
+unsafe fn output_event(event: &FileAccessEvent, path: &ResolvedPath) -> Result<(), i64> {
+    output_event_in(event, path, Scratch::Task)
+}
+
+/// Like [`output_event`], building the record in the `scratch` slot.
+#[inline(always)]
+unsafe fn output_event_in(
+    event: &FileAccessEvent,
+    path: &ResolvedPath,
+    scratch: Scratch,
+) -> Result<(), i64> {
+    let mut record = Record::<FileAccessEvent>::new(scratch)?;
+    *record.header() = *event;
+    record.push_path(path)?;
+    record.output(&EVENTS)
+}
+
+/// Builds a [`MetaOpEvent`] record, lets `fill` populate it and its paths, and sends it to
+/// `META_EVENTS`.
+#[inline(always)]
+unsafe fn output_meta_event(
+    event_type: EventType,
+    fill: impl FnOnce(&mut Record<MetaOpEvent>) -> Result<(), i64>,
+) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let mut record = Record::<MetaOpEvent>::new(Scratch::Task)?;
+    let meta = &mut *record.header();
+    meta.event.event_type = event_type;
+    meta.event.populate_from_current(tgid, tid)?;
+
+    fill(&mut record)?;
+    record.output(&META_EVENTS)
+}
+
 #[inline(always)]
 unsafe fn total_iovec_len(vec: *const iovec, vlen: usize) -> Result<u64, i64> {
     let mut total: u64 = 0;
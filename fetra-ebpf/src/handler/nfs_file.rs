@@ -0,0 +1,66 @@
+use crate::bindings::kiocb;
+use crate::event_ext::EventExt;
+use crate::handler::output_event;
+use crate::helpers::filter_tgids;
+use aya_ebpf::helpers::{bpf_ktime_get_ns, bpf_probe_read_kernel};
+use aya_ebpf::programs::{ProbeContext, RetProbeContext};
+use aya_ebpf::EbpfContext;
+use aya_ebpf::{macros::map, maps::HashMap};
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent};
+
+#[derive(Clone, Copy)]
+pub struct NfsCall {
+    iocb: *const kiocb,
+    start_ns: u64,
+}
+
+/// `nfs_file_read`/`nfs_file_write` calls in flight keyed by thread.
+#[map(name = "NFS_CALLS")]
+static mut NFS_CALLS: HashMap<u32, NfsCall> = HashMap::with_max_entries(10240, 0);
+
+/// NFS is usually a module, out of reach of fentry, so these are kprobes and read everything
+/// with `bpf_probe_read_kernel`.
+pub(crate) unsafe fn try_handle_nfs_file_io(ctx: &ProbeContext) -> Result<(), i64> {
+    let Some((_tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let Some(iocb) = ctx.arg::<*const kiocb>(0) else {
+        return Err(-1);
+    };
+
+    let call = NfsCall {
+        iocb,
+        start_ns: bpf_ktime_get_ns(),
+    };
+    NFS_CALLS.insert(&tid, &call, 0)
+}
+
+pub(crate) unsafe fn try_handle_nfs_file_io_exit(
+    ctx: &RetProbeContext,
+    event_type: EventType,
+) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let Some(call) = NFS_CALLS.get(&tid).copied() else {
+        return Ok(());
+    };
+    let _ = NFS_CALLS.remove(&tid);
+
+    let ret: i64 = ctx.ret().unwrap_or(-1);
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = event_type;
+    event.populate_from_current(tgid, tid)?;
+    event.duration_ns = bpf_ktime_get_ns().saturating_sub(call.start_ns);
+    event.bytes = ret.max(0) as u64;
+
+    let file = bpf_probe_read_kernel(&(*call.iocb).ki_filp)?;
+    let path = event.populate_from_path(bpf_probe_read_kernel(&(*file).f_path)?, ctx.as_ptr())?;
+
+    output_event(&event, &path)?;
+    Ok(())
+}
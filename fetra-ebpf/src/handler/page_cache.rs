@@ -0,0 +1,72 @@
+use crate::bindings::{address_space, folio, inode, readahead_control};
+use crate::folio::{folio_inode, folio_nr_pages};
+use crate::helpers::{atomic_add, filter_tgids, get_or_insert_zeroed, inode_id};
+use aya_ebpf::helpers::bpf_probe_read_kernel;
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::{macros::map, maps::LruHashMap};
+use fetra_common::{PageCacheKey, PageCacheStats};
+
+/// Page cache counters per file, polled by userspace.
+///
+/// These hooks fire for every page, so nothing goes through the ring buffer.
+#[map(name = "PAGE_CACHE_STATS")]
+static mut PAGE_CACHE_STATS: LruHashMap<PageCacheKey, PageCacheStats> =
+    LruHashMap::with_max_entries(16384, 0);
+
+#[inline(always)]
+unsafe fn stats_for(inode: *const inode) -> Result<*mut PageCacheStats, i64> {
+    let (dev, ino) = inode_id(inode)?;
+    get_or_insert_zeroed(&PAGE_CACHE_STATS, &PageCacheKey::new(dev, ino))
+}
+
+pub(crate) unsafe fn try_handle_folio_mark_accessed(ctx: &FEntryContext) -> Result<(), i64> {
+    if filter_tgids().is_none() {
+        return Ok(());
+    }
+
+    let folio: *const folio = ctx.arg(0);
+    let Some(inode) = folio_inode(folio)? else {
+        return Ok(());
+    };
+
+    let stats = stats_for(inode)?;
+    atomic_add(&mut (*stats).accessed, folio_nr_pages(folio)?);
+    Ok(())
+}
+
+pub(crate) unsafe fn try_handle_filemap_add_folio(ctx: &FEntryContext) -> Result<(), i64> {
+    if filter_tgids().is_none() {
+        return Ok(());
+    }
+
+    let mapping: *const address_space = ctx.arg(0);
+    let folio: *const folio = ctx.arg(1);
+
+    let inode = bpf_probe_read_kernel(&(*mapping).host)?;
+    if inode.is_null() {
+        return Ok(());
+    }
+
+    let stats = stats_for(inode)?;
+    atomic_add(&mut (*stats).added, folio_nr_pages(folio)?);
+    Ok(())
+}
+
+pub(crate) unsafe fn try_handle_page_cache_ra_unbounded(ctx: &FEntryContext) -> Result<(), i64> {
+    if filter_tgids().is_none() {
+        return Ok(());
+    }
+
+    let ractl: *const readahead_control = ctx.arg(0);
+    let nr_to_read: u64 = ctx.arg(1);
+
+    let mapping = bpf_probe_read_kernel(&(*ractl).mapping)?;
+    let inode = bpf_probe_read_kernel(&(*mapping).host)?;
+    if inode.is_null() {
+        return Ok(());
+    }
+
+    let stats = stats_for(inode)?;
+    atomic_add(&mut (*stats).readahead, nr_to_read);
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use crate::bindings::file;
+use crate::event_ext::EventExt;
+use crate::handler::{output_event, record_start, sync_range_len, take_elapsed};
+use crate::helpers::filter_tgids;
+use aya_ebpf::programs::{FEntryContext, FExitContext};
+use aya_ebpf::EbpfContext;
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent};
+
+pub(crate) unsafe fn try_handle_sync_file_range(ctx: &FEntryContext) -> Result<(), i64> {
+    let Some((_tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let file: *const file = ctx.arg(0);
+    record_start(tid, file)
+}
+
+pub(crate) unsafe fn try_handle_sync_file_range_exit(ctx: &FExitContext) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let file: *const file = ctx.arg(0);
+    let Some(duration_ns) = take_elapsed(tid, file) else {
+        return Ok(());
+    };
+
+    let offset: i64 = ctx.arg(1);
+    let nbytes: i64 = ctx.arg(2);
+
+    // nbytes == 0 means "everything from offset to the end of the file"
+    let end = if nbytes == 0 {
+        i64::MAX
+    } else {
+        offset.saturating_add(nbytes - 1)
+    };
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = EventType::SyncFileRange;
+    event.populate_from_current(tgid, tid)?;
+    event.duration_ns = duration_ns;
+    event.bytes = sync_range_len(file, offset, end);
+
+    let path = event.populate_from_file(file, ctx.as_ptr())?;
+
+    output_event(&event, &path)?;
+    Ok(())
+}
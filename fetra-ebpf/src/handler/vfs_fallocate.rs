@@ -0,0 +1,18 @@
+use crate::bindings::file;
+use crate::event_ext::EventExt;
+use crate::handler::output_meta_event;
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::EbpfContext;
+use fetra_common::EventType;
+
+pub(crate) unsafe fn try_handle_vfs_fallocate(ctx: &FEntryContext) -> Result<(), i64> {
+    let file: *const file = ctx.arg(0);
+    let len: i64 = ctx.arg(3);
+
+    output_meta_event(EventType::VfsFallocate, |record| {
+        let meta = &mut *record.header();
+        meta.event.bytes = len as u64;
+        let path = meta.event.populate_from_file(file, ctx.as_ptr())?;
+        record.push_path(&path)
+    })
+}
@@ -0,0 +1,47 @@
+use crate::bindings::file;
+use crate::event_ext::EventExt;
+use crate::handler::{output_event, record_start, sync_range_len, take_elapsed};
+use crate::helpers::filter_tgids;
+use aya_ebpf::programs::{FEntryContext, FExitContext};
+use aya_ebpf::EbpfContext;
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent};
+
+pub(crate) unsafe fn try_handle_vfs_fsync_range(ctx: &FEntryContext) -> Result<(), i64> {
+    let Some((_tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let file: *const file = ctx.arg(0);
+    record_start(tid, file)
+}
+
+pub(crate) unsafe fn try_handle_vfs_fsync_range_exit(ctx: &FExitContext) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let file: *const file = ctx.arg(0);
+    let Some(duration_ns) = take_elapsed(tid, file) else {
+        return Ok(());
+    };
+
+    let start: i64 = ctx.arg(1);
+    let end: i64 = ctx.arg(2);
+    let datasync: i32 = ctx.arg(3);
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = if datasync != 0 {
+        EventType::VfsFdatasync
+    } else {
+        EventType::VfsFsync
+    };
+    event.populate_from_current(tgid, tid)?;
+    event.duration_ns = duration_ns;
+    event.bytes = sync_range_len(file, start, end);
+
+    let path = event.populate_from_file(file, ctx.as_ptr())?;
+
+    output_event(&event, &path)?;
+    Ok(())
+}
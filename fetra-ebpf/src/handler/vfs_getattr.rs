@@ -0,0 +1,32 @@
+use crate::bindings::path;
+use crate::event_ext::EventExt;
+use crate::handler::output_event;
+use crate::helpers::{filter_tgids, sample_metadata};
+use aya_ebpf::helpers::bpf_probe_read_kernel;
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::EbpfContext;
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent};
+
+pub(crate) unsafe fn try_handle_vfs_getattr(ctx: &FEntryContext) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let Some(weight) = sample_metadata() else {
+        return Ok(());
+    };
+
+    let path: *const path = ctx.arg(0);
+    let path = bpf_probe_read_kernel(path)?;
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = EventType::VfsGetattr;
+    event.populate_from_current(tgid, tid)?;
+    event.weight = weight;
+
+    let path = event.populate_from_path(path, ctx.as_ptr())?;
+
+    output_event(&event, &path)?;
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use crate::bindings::{file, iov_iter, kiocb};
+use crate::event_ext::EventExt;
+use crate::handler::output_event;
+use crate::helpers::{filter_tgids, is_kernel_thread};
+use aya_ebpf::helpers::bpf_probe_read_kernel;
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::EbpfContext;
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent};
+
+pub(crate) unsafe fn try_handle_vfs_iocb_iter_read(ctx: &FEntryContext) -> Result<(), i64> {
+    handle_vfs_iocb_iter(ctx, EventType::VfsIocbRead)
+}
+
+pub(crate) unsafe fn try_handle_vfs_iocb_iter_write(ctx: &FEntryContext) -> Result<(), i64> {
+    handle_vfs_iocb_iter(ctx, EventType::VfsIocbWrite)
+}
+
+/// Only I/O that kernel threads issue on their own behalf is counted here, nfsd serving
+/// clients or cachefiles filling the cache, and it is attributed to that thread. Users calling
+/// in from a task are stacked filesystems forwarding a request to the real file: overlayfs
+/// backing files and FUSE passthrough. That request was already counted by the vfs, aio or
+/// io_uring probes against the file the process opened, under the process' tgid, and counting
+/// the forwarded copy would count the bytes twice.
+#[inline(always)]
+unsafe fn handle_vfs_iocb_iter(ctx: &FEntryContext, event_type: EventType) -> Result<(), i64> {
+    if !is_kernel_thread() {
+        return Ok(());
+    }
+
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let file: *const file = ctx.arg(0);
+    let iocb: *const kiocb = ctx.arg(1);
+    let iter: *const iov_iter = ctx.arg(2);
+
+    let count = bpf_probe_read_kernel(&(*iter).__bindgen_anon_1.__bindgen_anon_1.count)?;
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = event_type;
+    event.populate_from_current(tgid, tid)?;
+    event.bytes = count as u64;
+
+    event.populate_kiocb_flags(iocb)?;
+    let path = event.populate_from_file(file, ctx.as_ptr())?;
+
+    output_event(&event, &path)?;
+    Ok(())
+}
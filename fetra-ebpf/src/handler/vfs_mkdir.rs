@@ -0,0 +1,21 @@
+use crate::bindings::dentry;
+use crate::event_ext::EventExt;
+use crate::handler::output_meta_event;
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::EbpfContext;
+use fetra_common::EventType;
+
+const S_IFDIR: u16 = 0o040000;
+
+pub(crate) unsafe fn try_handle_vfs_mkdir(ctx: &FEntryContext) -> Result<(), i64> {
+    let dentry: *const dentry = ctx.arg(2);
+    let mode: u16 = ctx.arg(3);
+
+    output_meta_event(EventType::VfsMkdir, |record| {
+        let meta = &mut *record.header();
+        let path = meta.event.populate_from_dentry(dentry, ctx.as_ptr())?;
+        // the dentry is still negative on entry
+        meta.event.i_mode = S_IFDIR | mode;
+        record.push_path(&path)
+    })
+}
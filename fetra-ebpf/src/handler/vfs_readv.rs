@@ -1,9 +1,9 @@
 use crate::bindings::{file, iovec};
 use crate::event_ext::EventExt;
-use crate::handler::total_iovec_len;
+use crate::handler::{output_event, total_iovec_len, track_access, track_access_end};
 use crate::helpers::filter_tgids;
-use crate::EVENTS;
-use aya_ebpf::{helpers::bpf_get_current_comm, programs::FEntryContext, EbpfContext};
+use aya_ebpf::programs::{FEntryContext, FExitContext};
+use aya_ebpf::EbpfContext;
 use bytemuck::Zeroable;
 use fetra_common::{EventType, FileAccessEvent};
 
@@ -15,18 +15,30 @@ pub(crate) unsafe fn try_handle_vfs_readv(ctx: &FEntryContext) -> Result<(), i64
     let file: *const file = ctx.arg(0);
     let vec: *const iovec = ctx.arg(1);
     let vlen: usize = ctx.arg(2);
+    let pos: *const i64 = ctx.arg(3);
 
     let bytes = total_iovec_len(vec, vlen)?;
 
     let mut event = FileAccessEvent::zeroed();
     event.event_type = EventType::VfsReadv;
-    event.tid = tid;
-    event.tgid = tgid;
-    event.comm = bpf_get_current_comm()?;
+    event.populate_from_current(tgid, tid)?;
     event.bytes = bytes;
 
-    event.populate_from_file(file, ctx.as_ptr())?;
+    let path = event.populate_from_file(file, ctx.as_ptr())?;
+    track_access(&mut event, pos)?;
 
-    EVENTS.output(&event, 0)?;
+    output_event(&event, &path)?;
     Ok(())
 }
+
+pub(crate) unsafe fn try_handle_vfs_readv_exit(ctx: &FExitContext) -> Result<(), i64> {
+    let Some((tgid, _tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let file: *const file = ctx.arg(0);
+    let pos: *const i64 = ctx.arg(3);
+    let ret: isize = ctx.arg(5);
+
+    track_access_end(tgid, file, pos, ret)
+}
@@ -0,0 +1,24 @@
+use crate::bindings::{dentry, renamedata};
+use crate::d_path::{d_path_local, dentry_path};
+use crate::event_ext::EventExt;
+use crate::handler::output_meta_event;
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::EbpfContext;
+use fetra_common::EventType;
+
+pub(crate) unsafe fn try_handle_vfs_rename(ctx: &FEntryContext) -> Result<(), i64> {
+    let rd: *const renamedata = ctx.arg(0);
+    let old_dentry: *mut dentry = (*rd).old_dentry;
+    let new_dentry: *mut dentry = (*rd).new_dentry;
+
+    output_meta_event(EventType::VfsRename, |record| {
+        let path = (*record.header())
+            .event
+            .populate_from_dentry(old_dentry, ctx.as_ptr())?;
+        // d_path_local reuses the same per-cpu buffer, so the old name must be copied out first
+        record.push_path(&path)?;
+
+        let new_path = d_path_local(ctx.as_ptr(), dentry_path(new_dentry)?);
+        record.push_new_path(&new_path)
+    })
+}
@@ -0,0 +1,32 @@
+use crate::bindings::dentry;
+use crate::event_ext::EventExt;
+use crate::handler::output_event;
+use crate::helpers::{filter_tgids, sample_metadata};
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::EbpfContext;
+use bytemuck::Zeroable;
+use fetra_common::{EventType, FileAccessEvent};
+
+pub(crate) unsafe fn try_handle_vfs_setxattr(ctx: &FEntryContext) -> Result<(), i64> {
+    let Some((tgid, tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let Some(weight) = sample_metadata() else {
+        return Ok(());
+    };
+
+    let dentry: *const dentry = ctx.arg(1);
+    let size: u64 = ctx.arg(4);
+
+    let mut event = FileAccessEvent::zeroed();
+    event.event_type = EventType::VfsSetxattr;
+    event.populate_from_current(tgid, tid)?;
+    event.bytes = size;
+    event.weight = weight;
+
+    let path = event.populate_from_dentry(dentry, ctx.as_ptr())?;
+
+    output_event(&event, &path)?;
+    Ok(())
+}
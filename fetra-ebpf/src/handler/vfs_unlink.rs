@@ -0,0 +1,17 @@
+use crate::bindings::dentry;
+use crate::event_ext::EventExt;
+use crate::handler::output_meta_event;
+use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::EbpfContext;
+use fetra_common::EventType;
+
+pub(crate) unsafe fn try_handle_vfs_unlink(ctx: &FEntryContext) -> Result<(), i64> {
+    let dentry: *const dentry = ctx.arg(2);
+
+    output_meta_event(EventType::VfsUnlink, |record| {
+        let path = (*record.header())
+            .event
+            .populate_from_dentry(dentry, ctx.as_ptr())?;
+        record.push_path(&path)
+    })
+}
@@ -1,9 +1,8 @@
 use crate::bindings::file;
 use crate::event_ext::EventExt;
+use crate::handler::{output_event, track_access, track_access_end};
 use crate::helpers::filter_tgids;
-use crate::EVENTS;
-use aya_ebpf::helpers::bpf_get_current_comm;
-use aya_ebpf::programs::FEntryContext;
+use aya_ebpf::programs::{FEntryContext, FExitContext};
 use aya_ebpf::EbpfContext;
 use bytemuck::Zeroable;
 use fetra_common::{EventType, FileAccessEvent};
@@ -15,18 +14,30 @@ pub(crate) unsafe fn try_handle_vfs_write(ctx: &FEntryContext) -> Result<(), i64
 
     let file: *const file = ctx.arg(0);
     let count: u64 = ctx.arg(2);
+    let pos: *const i64 = ctx.arg(3);
 
     let mut event = FileAccessEvent::zeroed();
 
     event.event_type = EventType::VfsWrite;
-    event.tid = tid;
-    event.tgid = tgid;
-    event.comm = bpf_get_current_comm()?;
+    event.populate_from_current(tgid, tid)?;
     event.bytes = count;
 
-    event.populate_from_file(file, ctx.as_ptr())?;
+    let path = event.populate_from_file(file, ctx.as_ptr())?;
+    track_access(&mut event, pos)?;
 
-    EVENTS.output(&event, 0)?;
+    output_event(&event, &path)?;
 
     Ok(())
 }
+
+pub(crate) unsafe fn try_handle_vfs_write_exit(ctx: &FExitContext) -> Result<(), i64> {
+    let Some((tgid, _tid)) = filter_tgids() else {
+        return Ok(());
+    };
+
+    let file: *const file = ctx.arg(0);
+    let pos: *const i64 = ctx.arg(3);
+    let ret: isize = ctx.arg(4);
+
+    track_access_end(tgid, file, pos, ret)
+}
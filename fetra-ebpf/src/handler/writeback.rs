@@ -0,0 +1,84 @@
+use crate::bindings::{address_space, folio, inode, writeback_control};
+use crate::folio::folio_nr_pages;
+use crate::helpers::{atomic_add, filter_tgids, get_or_insert_zeroed, inode_id};
+use crate::PAGE_SIZE;
+use aya_ebpf::helpers::{bpf_get_current_cgroup_id, bpf_probe_read_kernel};
+use aya_ebpf::programs::BtfTracePointContext;
+use aya_ebpf::{macros::map, maps::LruHashMap};
+use fetra_common::{WritebackKey, WritebackStats};
+
+/// Dirtied and written back bytes per file and cgroup, polled by userspace.
+#[map(name = "WRITEBACK_STATS")]
+static mut WRITEBACK_STATS: LruHashMap<WritebackKey, WritebackStats> =
+    LruHashMap::with_max_entries(16384, 0);
+
+#[inline(always)]
+unsafe fn stats_for(inode: *const inode, cgroup_id: u64) -> Result<*mut WritebackStats, i64> {
+    let (dev, ino) = inode_id(inode)?;
+    get_or_insert_zeroed(&WRITEBACK_STATS, &WritebackKey::new(dev, ino, cgroup_id))
+}
+
+/// cgroup v2 id of the writeback context the inode is attached to, 0 without cgroup writeback.
+#[inline(always)]
+unsafe fn inode_wb_cgroup_id(inode: *const inode) -> Result<u64, i64> {
+    let wb = bpf_probe_read_kernel(&(*inode).i_wb)?;
+    if wb.is_null() {
+        return Ok(0);
+    }
+
+    let css = bpf_probe_read_kernel(&(*wb).memcg_css)?;
+    if css.is_null() {
+        return Ok(0);
+    }
+
+    let cgroup = bpf_probe_read_kernel(&(*css).cgroup)?;
+    let kn = bpf_probe_read_kernel(&(*cgroup).kn)?;
+    bpf_probe_read_kernel(&(*kn).id)
+}
+
+/// `writeback_dirty_folio` fires once per clean -> dirty transition, in the context of the task
+/// dirtying the folio. This covers `folio_mark_dirty` from `page_mkwrite` as well, hooking it
+/// directly would also count folios that are already dirty.
+pub(crate) unsafe fn try_handle_writeback_dirty_folio(
+    ctx: &BtfTracePointContext,
+) -> Result<(), i64> {
+    if filter_tgids().is_none() {
+        return Ok(());
+    }
+
+    let folio: *const folio = ctx.arg(0);
+    let mapping: *const address_space = ctx.arg(1);
+    if mapping.is_null() {
+        return Ok(());
+    }
+
+    let inode = bpf_probe_read_kernel(&(*mapping).host)?;
+    if inode.is_null() {
+        return Ok(());
+    }
+
+    let stats = stats_for(inode, bpf_get_current_cgroup_id())?;
+    atomic_add(
+        &mut (*stats).dirtied_bytes,
+        folio_nr_pages(folio)? * PAGE_SIZE,
+    );
+    Ok(())
+}
+
+pub(crate) unsafe fn try_handle_writeback_single_inode(
+    ctx: &BtfTracePointContext,
+) -> Result<(), i64> {
+    let inode: *const inode = ctx.arg(0);
+    let wbc: *const writeback_control = ctx.arg(1);
+    let nr_to_write: i64 = ctx.arg(2);
+
+    let left = bpf_probe_read_kernel(&(*wbc).nr_to_write)?;
+    let wrote = nr_to_write.saturating_sub(left);
+    if wrote <= 0 {
+        return Ok(());
+    }
+
+    let stats = stats_for(inode, inode_wb_cgroup_id(inode)?)?;
+    atomic_add(&mut (*stats).written_bytes, wrote as u64 * PAGE_SIZE);
+    Ok(())
+}
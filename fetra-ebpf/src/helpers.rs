@@ -1,5 +1,24 @@
-use crate::FILTER_TGIDS;
-use aya_ebpf::helpers::bpf_get_current_pid_tgid;
+use crate::bindings::{inode, super_block, task_struct};
+use crate::{FILTER_TGIDS, METADATA_SAMPLE_RATE};
+use aya_ebpf::helpers::{
+    bpf_get_current_pid_tgid, bpf_get_current_task_btf, bpf_get_prandom_u32, bpf_probe_read_kernel,
+};
+use aya_ebpf::maps::LruHashMap;
+use bytemuck::Zeroable;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+const PF_KTHREAD: u32 = 0x0020_0000;
+pub(crate) const BPF_NOEXIST: u64 = 1;
+
+/// Slot of the per-CPU buffers paths and records are built in.
+///
+/// Programs running in interrupt context get their own slot: they can interrupt a task-context
+/// program on the same CPU half-way through building a path or record in the other one.
+#[derive(Clone, Copy)]
+pub(crate) enum Scratch {
+    Task = 0,
+    Irq = 1,
+}
 
 pub(crate) unsafe fn filter_tgids() -> Option<(u32, u32)> {
     let pid_tgid = bpf_get_current_pid_tgid();
@@ -13,3 +32,49 @@ pub(crate) unsafe fn filter_tgids() -> Option<(u32, u32)> {
 
     Some((tgid, tid))
 }
+
+/// Randomly keeps one out of `METADATA_SAMPLE_RATE` calls and returns the weight of the kept one.
+#[inline(always)]
+pub(crate) unsafe fn sample_metadata() -> Option<u32> {
+    let rate = METADATA_SAMPLE_RATE;
+    if rate > 1 && bpf_get_prandom_u32() % rate != 0 {
+        return None;
+    }
+
+    Some(rate.max(1))
+}
+
+#[inline(always)]
+pub(crate) unsafe fn is_kernel_thread() -> bool {
+    let task = bpf_get_current_task_btf() as *mut task_struct;
+    (*task).flags & PF_KTHREAD != 0
+}
+
+/// Returns the value stored under `key`, inserting a zeroed one first if needed.
+#[inline(always)]
+pub(crate) unsafe fn get_or_insert_zeroed<K, V: Zeroable>(
+    map: &LruHashMap<K, V>,
+    key: &K,
+) -> Result<*mut V, i64> {
+    if let Some(value) = map.get_ptr_mut(key) {
+        return Ok(value);
+    }
+
+    // another CPU may have created the entry in the meantime
+    let _ = map.insert(key, &V::zeroed(), BPF_NOEXIST);
+    map.get_ptr_mut(key).ok_or(-1)
+}
+
+#[inline(always)]
+pub(crate) unsafe fn atomic_add(counter: *mut u64, n: u64) {
+    AtomicU64::from_ptr(counter).fetch_add(n, Ordering::Relaxed);
+}
+
+/// `(s_dev, i_ino)` pair identifying the inode.
+#[inline(always)]
+pub(crate) unsafe fn inode_id(inode: *const inode) -> Result<(u32, u64), i64> {
+    let sb: *const super_block = bpf_probe_read_kernel(&(*inode).i_sb)?;
+    let dev = bpf_probe_read_kernel(&(*sb).s_dev)?;
+    let ino = bpf_probe_read_kernel(&(*inode).i_ino)?;
+    Ok((dev, ino))
+}
@@ -15,20 +15,61 @@ pub mod bindings;
 
 mod d_path;
 mod event_ext;
+mod folio;
 pub mod ext;
 mod handler;
 mod helpers;
 mod macros;
+mod record;
 
+use crate::handler::aio::{try_handle_aio_read, try_handle_aio_write};
+use crate::handler::block_rq::{
+    try_handle_block_rq_complete, try_handle_block_rq_insert, try_handle_block_rq_issue,
+};
+use crate::handler::do_page_mkwrite::try_handle_do_page_mkwrite_exit;
+use crate::handler::do_truncate::try_handle_do_truncate;
 use crate::handler::filemap_fault::try_handle_filemap_fault;
-use crate::handler::vfs_read::try_handle_vfs_read;
-use crate::handler::vfs_readv::try_handle_vfs_readv;
-use crate::handler::vfs_write::try_handle_vfs_write;
-use crate::handler::vfs_writev::try_handle_vfs_writev;
-use aya_ebpf::macros::{fentry, fexit};
-use aya_ebpf::programs::{FEntryContext, FExitContext};
+use crate::handler::filemap_map_pages::{
+    try_handle_do_set_pte, try_handle_filemap_map_pages, try_handle_filemap_map_pages_exit,
+    try_handle_set_pte_range,
+};
+use crate::handler::io_uring::{try_handle_io_uring_complete, try_handle_io_uring_submit_req};
+use crate::handler::iterate_dir::try_handle_iterate_dir;
+use crate::handler::nfs_file::{try_handle_nfs_file_io, try_handle_nfs_file_io_exit};
+use crate::handler::page_cache::{
+    try_handle_filemap_add_folio, try_handle_folio_mark_accessed,
+    try_handle_page_cache_ra_unbounded,
+};
+use crate::handler::sync_file_range::{
+    try_handle_sync_file_range, try_handle_sync_file_range_exit,
+};
+use crate::handler::vfs_fallocate::try_handle_vfs_fallocate;
+use crate::handler::vfs_fsync_range::{
+    try_handle_vfs_fsync_range, try_handle_vfs_fsync_range_exit,
+};
+use crate::handler::vfs_getattr::try_handle_vfs_getattr;
+use crate::handler::vfs_getxattr::try_handle_vfs_getxattr;
+use crate::handler::vfs_iocb_iter::{
+    try_handle_vfs_iocb_iter_read, try_handle_vfs_iocb_iter_write,
+};
+use crate::handler::vfs_mkdir::try_handle_vfs_mkdir;
+use crate::handler::vfs_read::{try_handle_vfs_read, try_handle_vfs_read_exit};
+use crate::handler::vfs_readv::{try_handle_vfs_readv, try_handle_vfs_readv_exit};
+use crate::handler::vfs_rename::try_handle_vfs_rename;
+use crate::handler::vfs_rmdir::try_handle_vfs_rmdir;
+use crate::handler::vfs_setxattr::try_handle_vfs_setxattr;
+use crate::handler::vfs_unlink::try_handle_vfs_unlink;
+use crate::handler::vfs_write::{try_handle_vfs_write, try_handle_vfs_write_exit};
+use crate::handler::vfs_writev::{try_handle_vfs_writev, try_handle_vfs_writev_exit};
+use crate::handler::writeback::{
+    try_handle_writeback_dirty_folio, try_handle_writeback_single_inode,
+};
+use aya_ebpf::macros::{btf_tracepoint, fentry, fexit, kprobe, kretprobe};
+use aya_ebpf::programs::{
+    BtfTracePointContext, FEntryContext, FExitContext, ProbeContext, RetProbeContext,
+};
 use aya_ebpf::{macros::map, maps::RingBuf};
-use fetra_common::FileAccessEvent;
+use fetra_common::{EventType, FileAccessEvent, MetaOpEvent};
 
 #[no_mangle]
 static mut FILTER_TGIDS: [u32; 16] = [0; 16];
@@ -36,12 +77,28 @@ static mut FILTER_TGIDS: [u32; 16] = [0; 16];
 #[no_mangle]
 static mut PAGE_SIZE: u64 = 4096;
 
+#[no_mangle]
+static mut METADATA_SAMPLE_RATE: u32 = 1;
+
+/// Inode number of the host mount namespace, paths are resolved from the task root when 0.
+#[no_mangle]
+static mut HOST_MNT_NS: u32 = 0;
+
+/// Room for typical paths, longer ones take the space of several events.
+const AVG_PATH_LEN: usize = 256;
+
 const N_EVENTS: usize = 16 * 1024;
-const RB_CAP: u32 = (N_EVENTS * size_of::<FileAccessEvent>()) as u32;
+const RB_CAP: u32 = (N_EVENTS * (size_of::<FileAccessEvent>() + AVG_PATH_LEN)) as u32;
 
 #[map(name = "EVENTS")]
 static mut EVENTS: RingBuf = RingBuf::with_byte_size(RB_CAP, 0);
 
+const N_META_EVENTS: usize = 4 * 1024;
+const META_RB_CAP: u32 = (N_META_EVENTS * (size_of::<MetaOpEvent>() + 2 * AVG_PATH_LEN)) as u32;
+
+#[map(name = "META_EVENTS")]
+static mut META_EVENTS: RingBuf = RingBuf::with_byte_size(META_RB_CAP, 0);
+
 #[fentry(function = "handle_vfs_write")]
 pub fn handle_vfs_write(ctx: FEntryContext) -> i64 {
     match unsafe { try_handle_vfs_write(&ctx) } {
@@ -50,6 +107,14 @@ pub fn handle_vfs_write(ctx: FEntryContext) -> i64 {
     }
 }
 
+#[fexit(function = "vfs_write")]
+pub fn handle_vfs_write_exit(ctx: FExitContext) -> i64 {
+    match unsafe { try_handle_vfs_write_exit(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
 #[fentry(function = "handle_vfs_writev")]
 pub fn handle_vfs_writev(ctx: FEntryContext) -> i64 {
     match unsafe { try_handle_vfs_writev(&ctx) } {
@@ -58,6 +123,14 @@ pub fn handle_vfs_writev(ctx: FEntryContext) -> i64 {
     }
 }
 
+#[fexit(function = "vfs_writev")]
+pub fn handle_vfs_writev_exit(ctx: FExitContext) -> i64 {
+    match unsafe { try_handle_vfs_writev_exit(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
 #[fexit(function = "handle_filemap_fault")]
 pub fn handle_filemap_fault(ctx: FExitContext) -> i64 {
     match unsafe { try_handle_filemap_fault(&ctx) } {
@@ -66,6 +139,46 @@ pub fn handle_filemap_fault(ctx: FExitContext) -> i64 {
     }
 }
 
+#[fexit(function = "do_page_mkwrite")]
+pub fn handle_do_page_mkwrite_exit(ctx: FExitContext) -> i64 {
+    match unsafe { try_handle_do_page_mkwrite_exit(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "filemap_map_pages")]
+pub fn handle_filemap_map_pages(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_filemap_map_pages(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "set_pte_range")]
+pub fn handle_set_pte_range(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_set_pte_range(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "do_set_pte")]
+pub fn handle_do_set_pte(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_do_set_pte(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fexit(function = "filemap_map_pages")]
+pub fn handle_filemap_map_pages_exit(ctx: FExitContext) -> i64 {
+    match unsafe { try_handle_filemap_map_pages_exit(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
 #[fentry(function = "vfs_read")]
 pub fn handle_vfs_read(ctx: FEntryContext) -> i64 {
     match unsafe { try_handle_vfs_read(&ctx) } {
@@ -74,6 +187,14 @@ pub fn handle_vfs_read(ctx: FEntryContext) -> i64 {
     }
 }
 
+#[fexit(function = "vfs_read")]
+pub fn handle_vfs_read_exit(ctx: FExitContext) -> i64 {
+    match unsafe { try_handle_vfs_read_exit(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
 #[fentry(function = "vfs_readv")]
 pub fn handle_vfs_readv(ctx: FEntryContext) -> i64 {
     match unsafe { try_handle_vfs_readv(&ctx) } {
@@ -82,6 +203,270 @@ pub fn handle_vfs_readv(ctx: FEntryContext) -> i64 {
     }
 }
 
+#[fexit(function = "vfs_readv")]
+pub fn handle_vfs_readv_exit(ctx: FExitContext) -> i64 {
+    match unsafe { try_handle_vfs_readv_exit(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_fsync_range")]
+pub fn handle_vfs_fsync_range(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_fsync_range(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fexit(function = "vfs_fsync_range")]
+pub fn handle_vfs_fsync_range_exit(ctx: FExitContext) -> i64 {
+    match unsafe { try_handle_vfs_fsync_range_exit(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "sync_file_range")]
+pub fn handle_sync_file_range(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_sync_file_range(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fexit(function = "sync_file_range")]
+pub fn handle_sync_file_range_exit(ctx: FExitContext) -> i64 {
+    match unsafe { try_handle_sync_file_range_exit(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_unlink")]
+pub fn handle_vfs_unlink(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_unlink(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_rename")]
+pub fn handle_vfs_rename(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_rename(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_mkdir")]
+pub fn handle_vfs_mkdir(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_mkdir(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_rmdir")]
+pub fn handle_vfs_rmdir(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_rmdir(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "do_truncate")]
+pub fn handle_do_truncate(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_do_truncate(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_fallocate")]
+pub fn handle_vfs_fallocate(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_fallocate(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_getattr")]
+pub fn handle_vfs_getattr(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_getattr(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "iterate_dir")]
+pub fn handle_iterate_dir(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_iterate_dir(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_getxattr")]
+pub fn handle_vfs_getxattr(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_getxattr(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_setxattr")]
+pub fn handle_vfs_setxattr(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_setxattr(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "aio_read")]
+pub fn handle_aio_read(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_aio_read(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "aio_write")]
+pub fn handle_aio_write(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_aio_write(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_iocb_iter_read")]
+pub fn handle_vfs_iocb_iter_read(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_iocb_iter_read(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "vfs_iocb_iter_write")]
+pub fn handle_vfs_iocb_iter_write(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_vfs_iocb_iter_write(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "folio_mark_accessed")]
+pub fn handle_folio_mark_accessed(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_folio_mark_accessed(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "filemap_add_folio")]
+pub fn handle_filemap_add_folio(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_filemap_add_folio(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[fentry(function = "page_cache_ra_unbounded")]
+pub fn handle_page_cache_ra_unbounded(ctx: FEntryContext) -> i64 {
+    match unsafe { try_handle_page_cache_ra_unbounded(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+#[btf_tracepoint(function = "io_uring_submit_req")]
+pub fn handle_io_uring_submit_req(ctx: BtfTracePointContext) -> i32 {
+    match unsafe { try_handle_io_uring_submit_req(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e as i32,
+    }
+}
+
+#[btf_tracepoint(function = "io_uring_complete")]
+pub fn handle_io_uring_complete(ctx: BtfTracePointContext) -> i32 {
+    match unsafe { try_handle_io_uring_complete(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e as i32,
+    }
+}
+
+#[btf_tracepoint(function = "writeback_dirty_folio")]
+pub fn handle_writeback_dirty_folio(ctx: BtfTracePointContext) -> i32 {
+    match unsafe { try_handle_writeback_dirty_folio(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e as i32,
+    }
+}
+
+#[btf_tracepoint(function = "writeback_single_inode")]
+pub fn handle_writeback_single_inode(ctx: BtfTracePointContext) -> i32 {
+    match unsafe { try_handle_writeback_single_inode(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e as i32,
+    }
+}
+
+#[btf_tracepoint(function = "block_rq_insert")]
+pub fn handle_block_rq_insert(ctx: BtfTracePointContext) -> i32 {
+    match unsafe { try_handle_block_rq_insert(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e as i32,
+    }
+}
+
+#[btf_tracepoint(function = "block_rq_issue")]
+pub fn handle_block_rq_issue(ctx: BtfTracePointContext) -> i32 {
+    match unsafe { try_handle_block_rq_issue(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e as i32,
+    }
+}
+
+#[btf_tracepoint(function = "block_rq_complete")]
+pub fn handle_block_rq_complete(ctx: BtfTracePointContext) -> i32 {
+    match unsafe { try_handle_block_rq_complete(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e as i32,
+    }
+}
+
+#[kprobe]
+pub fn handle_nfs_file_read(ctx: ProbeContext) -> u32 {
+    match unsafe { try_handle_nfs_file_io(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e as u32,
+    }
+}
+
+#[kretprobe]
+pub fn handle_nfs_file_read_exit(ctx: RetProbeContext) -> u32 {
+    match unsafe { try_handle_nfs_file_io_exit(&ctx, EventType::NfsRead) } {
+        Ok(_) => 0,
+        Err(e) => e as u32,
+    }
+}
+
+#[kprobe]
+pub fn handle_nfs_file_write(ctx: ProbeContext) -> u32 {
+    match unsafe { try_handle_nfs_file_io(&ctx) } {
+        Ok(_) => 0,
+        Err(e) => e as u32,
+    }
+}
+
+#[kretprobe]
+pub fn handle_nfs_file_write_exit(ctx: RetProbeContext) -> u32 {
+    match unsafe { try_handle_nfs_file_io_exit(&ctx, EventType::NfsWrite) } {
+        Ok(_) => 0,
+        Err(e) => e as u32,
+    }
+}
+
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
@@ -0,0 +1,107 @@
+use crate::d_path::ResolvedPath;
+use crate::helpers::Scratch;
+use aya_ebpf::helpers::bpf_probe_read_kernel_buf;
+use aya_ebpf::{macros::map, maps::PerCpuArray, maps::RingBuf};
+use core::marker::PhantomData;
+use core::mem::size_of;
+use core::ptr::write_bytes;
+use core::slice::{from_raw_parts, from_raw_parts_mut};
+use fetra_common::{FileAccessEvent, MetaOpEvent, PATH_MAX};
+
+/// Largest record: a meta event with two paths.
+const RECORD_MAX: usize = size_of::<MetaOpEvent>() + 2 * PATH_MAX;
+
+#[repr(C)]
+pub struct RecordBuf {
+    pub buf: [u8; RECORD_MAX],
+}
+
+#[map(name = "RECORD_HEAP")]
+static mut RECORD_HEAP: PerCpuArray<RecordBuf> = PerCpuArray::with_max_entries(2, 0);
+
+/// Ring buffer record under construction: a `T` header followed by path bytes.
+///
+/// Paths don't fit on the BPF stack and vary in length, so the record is assembled in a per-CPU
+/// buffer and copied into the ring buffer with its actual size.
+pub struct Record<T> {
+    base: *mut u8,
+    len: usize,
+    _header: PhantomData<T>,
+}
+
+impl<T> Record<T> {
+    /// Starts a record with a zeroed header in the `scratch` slot.
+    #[inline(always)]
+    pub unsafe fn new(scratch: Scratch) -> Result<Self, i64> {
+        let Some(heap) = RECORD_HEAP.get_ptr_mut(scratch as u32) else {
+            return Err(-1);
+        };
+        let base = heap as *mut u8;
+        write_bytes(base, 0, size_of::<T>());
+
+        Ok(Self {
+            base,
+            len: size_of::<T>(),
+            _header: PhantomData,
+        })
+    }
+
+    #[inline(always)]
+    pub fn header(&self) -> *mut T {
+        self.base as *mut T
+    }
+
+    /// Appends the path bytes and returns how many were written.
+    #[inline(always)]
+    unsafe fn append(&mut self, path: &ResolvedPath) -> Result<u16, i64> {
+        let len = path.len.min(PATH_MAX);
+        if len == 0 {
+            return Ok(0);
+        }
+        if self.len > RECORD_MAX - PATH_MAX {
+            return Err(-1);
+        }
+
+        let dst = from_raw_parts_mut(self.base.add(self.len), len);
+        bpf_probe_read_kernel_buf(path.buf, dst)?;
+        self.len += len;
+        Ok(len as u16)
+    }
+
+    #[inline(always)]
+    pub unsafe fn output(&self, ring: &RingBuf) -> Result<(), i64> {
+        let len = self.len.min(RECORD_MAX);
+        ring.output(from_raw_parts(self.base, len), 0)
+    }
+}
+
+impl Record<FileAccessEvent> {
+    #[inline(always)]
+    pub unsafe fn push_path(&mut self, path: &ResolvedPath) -> Result<(), i64> {
+        let event = &mut *self.header();
+        event.path_len = self.append(path)?;
+        event.path_status = path.status;
+        event.mnt_ns = path.mnt_ns;
+        Ok(())
+    }
+}
+
+impl Record<MetaOpEvent> {
+    /// Appends the affected path, must come before [`Self::push_new_path`].
+    #[inline(always)]
+    pub unsafe fn push_path(&mut self, path: &ResolvedPath) -> Result<(), i64> {
+        let meta = &mut *self.header();
+        meta.event.path_len = self.append(path)?;
+        meta.event.path_status = path.status;
+        meta.event.mnt_ns = path.mnt_ns;
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub unsafe fn push_new_path(&mut self, path: &ResolvedPath) -> Result<(), i64> {
+        let meta = &mut *self.header();
+        meta.new_path_len = self.append(path)?;
+        meta.new_path_status = path.status;
+        Ok(())
+    }
+}
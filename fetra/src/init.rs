@@ -28,6 +28,61 @@ pub(crate) fn setup_metrics() -> anyhow::Result<()> {
         .context("failed to install Prometheus recorder")?;
 
     metrics::describe_counter!("io", "I/O");
+    metrics::describe_histogram!(
+        "io_request_bytes",
+        metrics::Unit::Bytes,
+        "Size of vfs reads and writes per file and access pattern"
+    );
+    metrics::describe_counter!("fsync", "fsync, fdatasync and sync_file_range calls");
+    metrics::describe_counter!("fsync_bytes", "Length of the flushed file ranges");
+    metrics::describe_counter!(
+        "meta_ops",
+        "unlink, rename, mkdir, rmdir, truncate and fallocate calls"
+    );
+    metrics::describe_counter!(
+        "metadata_calls",
+        "stat, getdents and xattr calls by directory and process (sampled)"
+    );
+    metrics::describe_counter!(
+        "page_cache",
+        metrics::Unit::Count,
+        "Page cache hits, misses and readahead pages per file"
+    );
+    metrics::describe_counter!(
+        "writeback",
+        metrics::Unit::Bytes,
+        "Bytes dirtied in and written back from the page cache per file and cgroup"
+    );
+    metrics::describe_counter!(
+        "mmap_null_page",
+        metrics::Unit::Count,
+        "mmap faults that failed without mapping a page"
+    );
+    metrics::describe_counter!(
+        "block_io_bytes",
+        metrics::Unit::Bytes,
+        "Bytes transferred by block requests per device and file"
+    );
+    metrics::describe_histogram!(
+        "nfs_duration_seconds",
+        metrics::Unit::Seconds,
+        "Latency of NFS client reads and writes per server and export"
+    );
+    metrics::describe_histogram!(
+        "block_rq_queue_seconds",
+        metrics::Unit::Seconds,
+        "Time block requests spend queued in the I/O scheduler before being issued to the driver"
+    );
+    metrics::describe_histogram!(
+        "block_rq_duration_seconds",
+        metrics::Unit::Seconds,
+        "Time from issuing a block request to the driver until its completion"
+    );
+    metrics::describe_histogram!(
+        "fsync_duration_seconds",
+        metrics::Unit::Seconds,
+        "Time spent in fsync, fdatasync and sync_file_range"
+    );
 
     Ok(())
 }
@@ -1,20 +1,34 @@
 mod ebpf_ext;
 mod init;
+mod opt;
 mod process;
 mod types;
 
 use crate::ebpf_ext::EbpfExt;
 use crate::init::{set_rlimit, setup_metrics, MachineInfo};
-use crate::process::aggregator::Aggregator;
+use crate::opt::Opt;
+use crate::process::aggregator::{Aggregator, OptionalLabels};
+use crate::process::counters::{CounterDeltas, Counters};
+use crate::process::record::{Event, MetaOp};
+use crate::types::mount::{MountTable, MOUNTINFO};
 use anyhow::Context as _;
-use aya::maps::RingBuf;
-use aya::programs::FExit;
-use aya::{programs::FEntry, Btf, EbpfLoader};
-use fetra_common::FileAccessEvent;
+use aya::maps::{HashMap, MapData, RingBuf};
+use aya::programs::{BtfTracePoint, FExit, KProbe};
+use aya::Pod;
+use aya::{programs::FEntry, Btf, Ebpf, EbpfLoader};
+use clap::Parser;
 use log::{info, warn};
 use std::fmt::Display;
 use std::fs;
+use std::future::Future;
+use std::hash::Hash;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::MetadataExt;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
 
 fn get_ppid(pid: impl Display) -> anyhow::Result<u32> {
     Ok(fs::read_to_string(format!("/proc/{}/stat", pid))?
@@ -24,6 +38,12 @@ fn get_ppid(pid: impl Display) -> anyhow::Result<u32> {
         .parse::<u32>()?)
 }
 
+/// Mount namespace of init, which is the host one as long as we share its pid namespace.
+fn get_host_mnt_ns() -> anyhow::Result<u32> {
+    let meta = fs::metadata("/proc/1/ns/mnt").context("host mount namespace")?;
+    Ok(meta.ino() as u32)
+}
+
 fn get_ppid_path() -> anyhow::Result<[u32; 16]> {
     let pid = unsafe { libc::getpid() } as u32;
     let mut parent_pid = unsafe { libc::getppid() } as u32;
@@ -42,8 +62,186 @@ fn get_ppid_path() -> anyhow::Result<[u32; 16]> {
     Ok(pids)
 }
 
+/// Hands what the counters of a stats map grew by since the previous pass to `sink`.
+async fn poll_counters<K, S, F>(
+    name: &'static str,
+    stats: HashMap<MapData, K, S>,
+    sink: impl Fn(K, S) -> F,
+) where
+    K: Pod + Eq + Hash,
+    S: Pod + Counters,
+    F: Future<Output = ()>,
+{
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    let mut deltas = CounterDeltas::default();
+
+    loop {
+        interval.tick().await;
+        for item in stats.iter() {
+            let (key, value) = match item {
+                Ok(item) => item,
+                Err(err) => {
+                    warn!("Failed to read {name} stats: {err}");
+                    continue;
+                }
+            };
+            if let Some(delta) = deltas.update(key, value) {
+                sink(key, delta).await;
+            }
+        }
+        deltas.finish();
+    }
+}
+
+/// Re-reads the mount table whenever it changes, which `mountinfo` signals with `POLLPRI`.
+async fn watch_mounts(aggregator: Arc<Aggregator>) {
+    let file = match fs::File::open(MOUNTINFO) {
+        Ok(file) => file,
+        Err(err) => {
+            warn!("Failed to open {MOUNTINFO}, mount labels won't be updated: {err}");
+            return;
+        }
+    };
+    let fd = match AsyncFd::with_interest(file, Interest::PRIORITY) {
+        Ok(fd) => fd,
+        Err(err) => {
+            warn!("Failed to watch {MOUNTINFO}, mount labels won't be updated: {err}");
+            return;
+        }
+    };
+
+    loop {
+        let mut guard = match fd.ready(Interest::PRIORITY).await {
+            Ok(guard) => guard,
+            Err(err) => {
+                warn!("Failed to watch {MOUNTINFO}, mount labels won't be updated: {err}");
+                return;
+            }
+        };
+        guard.clear_ready();
+
+        match MountTable::read() {
+            Ok(mounts) => aggregator.set_mounts(mounts),
+            Err(err) => warn!("Failed to read mount table: {err}"),
+        }
+    }
+}
+
+/// Netlink socket receiving the kernel's uevents, the ones udev acts on.
+fn uevent_socket() -> io::Result<OwnedFd> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+            libc::NETLINK_KOBJECT_UEVENT,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as u16;
+    addr.nl_groups = 1;
+    let ret = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            size_of::<libc::sockaddr_nl>() as u32,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(fd)
+}
+
+/// Invalidates the cached device topology when block devices change (LVs created, disks
+/// hot-plugged, RAID members replaced).
+async fn watch_block_devices(aggregator: Arc<Aggregator>) {
+    let fd = match uevent_socket().and_then(AsyncFd::new) {
+        Ok(fd) => fd,
+        Err(err) => {
+            warn!("Failed to listen for uevents, device labels may get stale: {err}");
+            return;
+        }
+    };
+
+    let mut buf = [0u8; 8192];
+    loop {
+        let mut guard = match fd.readable().await {
+            Ok(guard) => guard,
+            Err(err) => {
+                warn!("Failed to listen for uevents, device labels may get stale: {err}");
+                return;
+            }
+        };
+        let received = guard.try_io(|fd| {
+            let ret = unsafe {
+                libc::recv(
+                    fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    0,
+                )
+            };
+            if ret < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(ret as usize)
+            }
+        });
+
+        match received {
+            // `add@/devices/...\0ACTION=add\0...\0SUBSYSTEM=block\0...`
+            Ok(Ok(len)) => {
+                if buf[..len]
+                    .split(|b| *b == 0)
+                    .any(|field| field == b"SUBSYSTEM=block")
+                {
+                    aggregator.invalidate_block_devices();
+                }
+            }
+            // ENOBUFS when events were dropped, one of them may have been about a block device
+            Ok(Err(err)) => {
+                warn!("Failed to receive uevent: {err}");
+                aggregator.invalidate_block_devices();
+            }
+            Err(_would_block) => continue,
+        }
+    }
+}
+
+/// Attaches the fault-around probes, counting pages on `set_pte_range` or, before 6.6, on
+/// `do_set_pte`. The entry probe goes last: the others do nothing without it.
+fn attach_fault_around(ebpf: &mut Ebpf, btf: &Btf) -> anyhow::Result<()> {
+    let program = ebpf.load_program::<FEntry>("handle_set_pte_range")?;
+    if program
+        .load("set_pte_range", btf)
+        .and_then(|_| program.attach().map(|_| ()))
+        .is_err()
+    {
+        let program = ebpf.load_program::<FEntry>("handle_do_set_pte")?;
+        program.load("do_set_pte", btf)?;
+        program.attach()?;
+    }
+
+    let program = ebpf.load_program::<FExit>("handle_filemap_map_pages_exit")?;
+    program.load("filemap_map_pages", btf)?;
+    program.attach()?;
+
+    let program = ebpf.load_program::<FEntry>("handle_filemap_map_pages")?;
+    program.load("filemap_map_pages", btf)?;
+    program.attach()?;
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let opt = Opt::parse();
+
     setup_metrics()?;
 
     let ppid_path = get_ppid_path()?;
@@ -57,12 +255,22 @@ async fn main() -> anyhow::Result<()> {
 
     set_rlimit();
 
+    let host_mnt_ns = if opt.host_paths {
+        let host_mnt_ns = get_host_mnt_ns()?;
+        info!("Resolving paths in host mount namespace: {}", host_mnt_ns);
+        host_mnt_ns
+    } else {
+        0
+    };
+
     let mut loader = EbpfLoader::new();
     let btf = Btf::from_sys_fs().ok();
     loader
         .btf(btf.as_ref())
         .set_global("FILTER_TGIDS", &ppid_path, true)
-        .set_global("PAGE_SIZE", &page_size, true);
+        .set_global("PAGE_SIZE", &page_size, true)
+        .set_global("METADATA_SAMPLE_RATE", &opt.metadata_sample_rate, true)
+        .set_global("HOST_MNT_NS", &host_mnt_ns, true);
 
     let mut ebpf = loader.load(aya::include_bytes_aligned!(concat!(
         env!("OUT_DIR"),
@@ -75,29 +283,210 @@ async fn main() -> anyhow::Result<()> {
 
     let btf = Btf::from_sys_fs().context("BTF from sysfs")?;
 
-    for syscall in ["vfs_write", "vfs_writev", "vfs_read", "vfs_readv"] {
+    for syscall in [
+        "vfs_write",
+        "vfs_writev",
+        "vfs_read",
+        "vfs_readv",
+        "vfs_fsync_range",
+        "sync_file_range",
+        "vfs_unlink",
+        "vfs_rename",
+        "vfs_mkdir",
+        "vfs_rmdir",
+        "do_truncate",
+        "vfs_fallocate",
+    ] {
         let program_name = format!("handle_{}", syscall);
         let program = ebpf.load_program::<FEntry>(&program_name)?;
         program.load(syscall, &btf)?;
         program.attach()?;
     }
 
+    if opt.metadata_calls {
+        for syscall in ["vfs_getattr", "iterate_dir", "vfs_getxattr", "vfs_setxattr"] {
+            let program_name = format!("handle_{}", syscall);
+            let program = ebpf.load_program::<FEntry>(&program_name)?;
+            program.load(syscall, &btf)?;
+            program.attach()?;
+        }
+    }
+
+    for syscall in [
+        "vfs_write",
+        "vfs_writev",
+        "vfs_read",
+        "vfs_readv",
+        "vfs_fsync_range",
+        "sync_file_range",
+    ] {
+        let program_name = format!("handle_{}_exit", syscall);
+        let program = ebpf.load_program::<FExit>(&program_name)?;
+        program.load(syscall, &btf)?;
+        program.attach()?;
+    }
+
     let program = ebpf.load_program::<FExit>("handle_filemap_fault")?;
     program.load("filemap_fault", &btf)?;
     program.attach()?;
 
-    let ring_buf = RingBuf::try_from(ebpf.map_mut("EVENTS").unwrap())?;
+    if let Err(err) = attach_fault_around(&mut ebpf, &btf) {
+        warn!("Failed to attach filemap_map_pages, fault-around pages won't be counted: {err}");
+    }
+
+    // static function, may be inlined into its callers
+    let program = ebpf.load_program::<FExit>("handle_do_page_mkwrite_exit")?;
+    if let Err(err) = program
+        .load("do_page_mkwrite", &btf)
+        .and_then(|_| program.attach().map(|_| ()))
+    {
+        warn!("Failed to attach do_page_mkwrite, mkwrite faults won't be counted: {err}");
+    }
+
+    // missing on older kernels, which had no in-kernel users of kiocb I/O on files
+    for function in ["vfs_iocb_iter_read", "vfs_iocb_iter_write"] {
+        let program_name = format!("handle_{}", function);
+        let program = ebpf.load_program::<FEntry>(&program_name)?;
+        if let Err(err) = program
+            .load(function, &btf)
+            .and_then(|_| program.attach().map(|_| ()))
+        {
+            warn!("Failed to attach {function}, kernel thread I/O won't be counted: {err}");
+        }
+    }
+
+    // static functions, may be inlined into `__io_submit_one`
+    for function in ["aio_read", "aio_write"] {
+        let program_name = format!("handle_{}", function);
+        let program = ebpf.load_program::<FEntry>(&program_name)?;
+        if let Err(err) = program
+            .load(function, &btf)
+            .and_then(|_| program.attach().map(|_| ()))
+        {
+            warn!("Failed to attach {function}, io_submit I/O won't be counted: {err}");
+        }
+    }
+
+    // missing without CONFIG_IO_URING or on kernels predating `io_uring_submit_req`, completion
+    // goes first so that submissions are never recorded without anything removing them again
+    for tracepoint in ["io_uring_complete", "io_uring_submit_req"] {
+        let program_name = format!("handle_{}", tracepoint);
+        let program = ebpf.load_program::<BtfTracePoint>(&program_name)?;
+        if let Err(err) = program
+            .load(tracepoint, &btf)
+            .and_then(|_| program.attach().map(|_| ()))
+        {
+            warn!("Failed to attach {tracepoint}, io_uring I/O won't be counted: {err}");
+            break;
+        }
+    }
+
+    let ring_buf = RingBuf::try_from(ebpf.take_map("EVENTS").unwrap())?;
     let mut async_ring = AsyncFd::new(ring_buf)?;
-    let aggregator = Aggregator::new(machine_info);
+    let meta_ring_buf = RingBuf::try_from(ebpf.take_map("META_EVENTS").unwrap())?;
+    let mut async_meta_ring = AsyncFd::new(meta_ring_buf)?;
+    let mounts = MountTable::read()?;
+    let aggregator = Arc::new(Aggregator::new(
+        machine_info,
+        mounts,
+        opt.cmd_label,
+        opt.interpreters.clone(),
+        opt.thread_names,
+        OptionalLabels {
+            mnt_ns: opt.host_paths,
+            users: opt.user_labels,
+            unit: opt.unit_labels,
+            parents: opt.parent_labels,
+            ancestry: opt.ancestry_labels,
+            mount: opt.mount_labels,
+            device: opt.device_labels,
+            backing: opt.backing_labels,
+            remote: opt.remote_labels,
+        },
+    ));
+    tokio::spawn(watch_mounts(aggregator.clone()));
+    tokio::spawn(watch_block_devices(aggregator.clone()));
 
-    loop {
-        let mut guard = async_ring.readable_mut().await?;
-        let ring_buf = guard.get_inner_mut();
-        while let Some(item) = ring_buf.next() {
-            let event = bytemuck::from_bytes::<FileAccessEvent>(&item);
-            aggregator.process_event(event).await?;
+    if opt.page_cache {
+        for function in [
+            "folio_mark_accessed",
+            "filemap_add_folio",
+            "page_cache_ra_unbounded",
+        ] {
+            let program_name = format!("handle_{}", function);
+            let program = ebpf.load_program::<FEntry>(&program_name)?;
+            program.load(function, &btf)?;
+            program.attach()?;
         }
 
-        guard.clear_ready();
+        let stats = HashMap::try_from(ebpf.take_map("PAGE_CACHE_STATS").unwrap())?;
+        let aggregator = aggregator.clone();
+        tokio::spawn(poll_counters("page cache", stats, move |key, delta| {
+            let aggregator = aggregator.clone();
+            async move { aggregator.process_page_cache_delta(&key, &delta).await }
+        }));
+    }
+
+    if opt.block {
+        for function in ["block_rq_insert", "block_rq_issue", "block_rq_complete"] {
+            let program_name = format!("handle_{}", function);
+            let program = ebpf.load_program::<BtfTracePoint>(&program_name)?;
+            program.load(function, &btf)?;
+            program.attach()?;
+        }
+    }
+
+    if opt.nfs {
+        for function in ["nfs_file_read", "nfs_file_write"] {
+            for program_name in [
+                format!("handle_{}", function),
+                format!("handle_{}_exit", function),
+            ] {
+                let program = ebpf.load_program::<KProbe>(&program_name)?;
+                program.load()?;
+                program.attach(function, 0)?;
+            }
+        }
+    }
+
+    if opt.writeback {
+        for function in ["writeback_dirty_folio", "writeback_single_inode"] {
+            let program_name = format!("handle_{}", function);
+            let program = ebpf.load_program::<BtfTracePoint>(&program_name)?;
+            program.load(function, &btf)?;
+            program.attach()?;
+        }
+
+        let stats = HashMap::try_from(ebpf.take_map("WRITEBACK_STATS").unwrap())?;
+        let aggregator = aggregator.clone();
+        tokio::spawn(poll_counters("writeback", stats, move |key, delta| {
+            let aggregator = aggregator.clone();
+            async move { aggregator.process_writeback_delta(&key, &delta).await }
+        }));
+    }
+
+    loop {
+        tokio::select! {
+            guard = async_ring.readable_mut() => {
+                let mut guard = guard?;
+                let ring_buf = guard.get_inner_mut();
+                while let Some(item) = ring_buf.next() {
+                    let event = Event::parse(&item)?;
+                    aggregator.process_event(&event).await?;
+                }
+
+                guard.clear_ready();
+            }
+            guard = async_meta_ring.readable_mut() => {
+                let mut guard = guard?;
+                let ring_buf = guard.get_inner_mut();
+                while let Some(item) = ring_buf.next() {
+                    let meta = MetaOp::parse(&item)?;
+                    aggregator.process_meta_event(&meta).await?;
+                }
+
+                guard.clear_ready();
+            }
+        }
     }
 }
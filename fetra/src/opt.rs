@@ -0,0 +1,94 @@
+use clap::{Parser, ValueEnum};
+
+#[derive(Debug, Parser)]
+pub(crate) struct Opt {
+    /// Trace vfs_getattr, iterate_dir, vfs_getxattr and vfs_setxattr calls
+    #[clap(long)]
+    pub(crate) metadata_calls: bool,
+
+    /// Emit one out of N metadata calls, counters are scaled back by N
+    #[clap(long, default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..))]
+    pub(crate) metadata_sample_rate: u32,
+
+    /// Count page cache hits, misses and readahead per file
+    #[clap(long)]
+    pub(crate) page_cache: bool,
+
+    /// Count dirtied and written back bytes per file and cgroup
+    #[clap(long)]
+    pub(crate) writeback: bool,
+
+    /// Trace block requests, with latency per device and bytes per file
+    #[clap(long)]
+    pub(crate) block: bool,
+
+    /// Measure NFS client read and write latency per server
+    #[clap(long)]
+    pub(crate) nfs: bool,
+
+    /// Resolve paths up to the host root instead of the root of the (containerized) task
+    #[clap(long)]
+    pub(crate) host_paths: bool,
+
+    /// What the `cmd` label names a process by
+    #[clap(long, value_enum, default_value_t = CmdLabel::Argv0)]
+    pub(crate) cmd_label: CmdLabel,
+
+    /// Interpreters named by the script or module they run with `--cmd-label script`
+    #[clap(
+        long,
+        value_delimiter = ',',
+        default_value = "python,perl,ruby,node,php,bash,sh"
+    )]
+    pub(crate) interpreters: Vec<String>,
+
+    /// Label events with the name of the calling thread, up to N distinct names per process
+    #[clap(long, value_name = "N", num_args = 0..=1, default_missing_value = "32")]
+    pub(crate) thread_names: Option<usize>,
+
+    /// Label events with the user and group of the caller and the owner and group of the file
+    #[clap(long)]
+    pub(crate) user_labels: bool,
+
+    /// Label events with the systemd service or scope of the caller
+    #[clap(long)]
+    pub(crate) unit_labels: bool,
+
+    /// Label events with the command of the caller's parent and top-level ancestor
+    #[clap(long)]
+    pub(crate) parent_labels: bool,
+
+    /// Label events with the full chain of processes above the caller, as in `sshd>bash>make`
+    #[clap(long)]
+    pub(crate) ancestry_labels: bool,
+
+    /// Label events with the mount point, source and options of the mount the file was accessed
+    /// through
+    #[clap(long)]
+    pub(crate) mount_labels: bool,
+
+    /// Label file events with the device mapper path, logical volume and physical disks of the
+    /// device
+    #[clap(long)]
+    pub(crate) device_labels: bool,
+
+    /// Label events with the overlay layer, backing filesystem and btrfs subvolume of the file
+    #[clap(long)]
+    pub(crate) backing_labels: bool,
+
+    /// Label file events with the server and export of files on network filesystems
+    #[clap(long)]
+    pub(crate) remote_labels: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum CmdLabel {
+    /// First argument of the command line, as set by the process
+    Argv0,
+    /// Full path of the executable
+    Exe,
+    /// File name of the executable
+    ExeBasename,
+    /// Script run by an interpreter, the executable's file name otherwise
+    Script,
+}
@@ -1,60 +1,311 @@
-use crate::process::event_ext::EventExt;
+use crate::opt::CmdLabel;
+use crate::process::event_ext::{basename, is_interpreter, EventExt};
+use crate::process::page_cache::PageCacheDelta;
+use crate::process::record::{Event, MetaOp};
 use crate::types;
-use fetra_common::FileAccessEvent;
+use crate::types::btrfs::Subvol;
+use crate::types::cgroup::{self, CgroupId};
+use crate::types::dev::{Dev, Topology};
+use crate::types::fs_type::FsType;
+use crate::types::mount::{Mount, MountTable};
+use crate::types::process::{Ancestry, Pid, ThreadNames};
+use crate::types::user::{Gid, Uid};
+use fetra_common::{
+    AccessPattern, EventType, PageCacheKey, PageCacheStats, WritebackKey, WritebackStats,
+};
+use log::debug;
 use metrics::Label;
 use moka::future::Cache;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use crate::init::MachineInfo;
 
+/// Labels that multiply the number of series and are only added when asked for
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptionalLabels {
+    /// Mount namespace of the caller, which tells apart host paths resolved from different containers
+    pub mnt_ns: bool,
+    /// User and group of the caller and owner and group of the file
+    pub users: bool,
+    /// Systemd service or scope the caller runs in
+    pub unit: bool,
+    /// Command of the parent and of the top-level ancestor of the caller
+    pub parents: bool,
+    /// Full chain of ancestors of the caller
+    pub ancestry: bool,
+    /// Mount point, source and options of the mount the file was accessed through
+    pub mount: bool,
+    /// Device mapper path, logical volume and physical disks of the device on file events, block
+    /// request metrics always have them
+    pub device: bool,
+    /// Overlay layer, backing filesystem and btrfs subvolume the file lives on
+    pub backing: bool,
+    /// Server and export of files on network filesystems, NFS latency metrics always have them
+    pub remote: bool,
+}
+
 pub struct Aggregator {
     cmd_name_by_tgid: Cache<u32, Arc<str>>,
+    ancestry_by_process: Cache<(u32, u64), Arc<Ancestry>>,
+    thread_names_by_process: Cache<(u32, u64), Arc<ThreadNames>>,
+    cgroup_path_by_id: Cache<u64, Arc<str>>,
+    user_name_by_uid: Cache<u32, Arc<str>>,
+    group_name_by_gid: Cache<u32, Arc<str>>,
     device_name_by_dev: Cache<u32, Arc<str>>,
+    topology_by_dev: Cache<u32, Arc<Topology>>,
     fs_type_by_magic: Cache<u64, Arc<str>>,
+    btrfs_subvol_by_dir: Cache<(u32, String), Arc<Subvol>>,
     file_type_by_mode: Cache<u32, Arc<str>>,
+    path_by_inode: Cache<(u32, u64), Arc<str>>,
+    mounts: RwLock<Arc<MountTable>>,
+    cmd_label: CmdLabel,
+    interpreters: Vec<String>,
+    max_thread_names: Option<usize>,
+    optional_labels: OptionalLabels,
     machine_info: MachineInfo
 }
 
 impl Aggregator {
-    pub fn new(machine_info: MachineInfo) -> Self {
+    pub fn new(
+        machine_info: MachineInfo,
+        mounts: MountTable,
+        cmd_label: CmdLabel,
+        interpreters: Vec<String>,
+        max_thread_names: Option<usize>,
+        optional_labels: OptionalLabels,
+    ) -> Self {
         Self {
             machine_info,
+            cmd_label,
+            interpreters,
+            max_thread_names,
+            optional_labels,
             cmd_name_by_tgid: Cache::builder()
                 .max_capacity(10000)
                 .time_to_idle(Duration::from_secs(5))
                 .time_to_live(Duration::from_secs(10))
                 .build(),
+            ancestry_by_process: Cache::builder()
+                .max_capacity(10000)
+                .time_to_idle(Duration::from_secs(60))
+                .time_to_live(Duration::from_secs(300))
+                .build(),
+            // kept as long as the process does I/O, or the cap would be reset
+            thread_names_by_process: Cache::builder()
+                .max_capacity(10000)
+                .time_to_idle(Duration::from_secs(300))
+                .build(),
+            cgroup_path_by_id: Cache::builder()
+                .max_capacity(1000)
+                .time_to_idle(Duration::from_secs(60))
+                .time_to_live(Duration::from_secs(300))
+                .build(),
+            user_name_by_uid: Cache::builder()
+                .max_capacity(1000)
+                .time_to_idle(Duration::from_secs(60))
+                .time_to_live(Duration::from_secs(300))
+                .build(),
+            group_name_by_gid: Cache::builder()
+                .max_capacity(1000)
+                .time_to_idle(Duration::from_secs(60))
+                .time_to_live(Duration::from_secs(300))
+                .build(),
             device_name_by_dev: Cache::builder()
                 .max_capacity(100)
                 .time_to_idle(Duration::from_secs(5))
                 .time_to_live(Duration::from_secs(10))
                 .build(),
+            topology_by_dev: Cache::builder()
+                .max_capacity(100)
+                .time_to_idle(Duration::from_secs(300))
+                .build(),
             fs_type_by_magic: Cache::builder()
                 .max_capacity(100)
                 .time_to_idle(Duration::from_secs(5))
                 .time_to_live(Duration::from_secs(10))
                 .build(),
+            btrfs_subvol_by_dir: Cache::builder()
+                .max_capacity(4096)
+                .time_to_idle(Duration::from_secs(60))
+                .time_to_live(Duration::from_secs(300))
+                .build(),
             file_type_by_mode: Cache::builder()
                 .max_capacity(100)
                 .time_to_idle(Duration::from_secs(5))
                 .time_to_live(Duration::from_secs(10))
                 .build(),
+            path_by_inode: Cache::builder()
+                .max_capacity(16384)
+                .time_to_idle(Duration::from_secs(60))
+                .time_to_live(Duration::from_secs(300))
+                .build(),
+            mounts: RwLock::new(Arc::new(mounts)),
         }
     }
-    pub async fn process_event(&self, event: &FileAccessEvent) -> Result<(), types::Error> {
-        let labels = self.get_labels(event).await;
-        metrics::counter!("io", labels).increment(event.bytes);
+
+    pub fn set_mounts(&self, mounts: MountTable) {
+        *self.mounts.write().unwrap() = Arc::new(mounts);
+    }
+
+    /// Forgets device names and topologies after block devices were added, removed or changed.
+    pub fn invalidate_block_devices(&self) {
+        self.device_name_by_dev.invalidate_all();
+        self.topology_by_dev.invalidate_all();
+    }
+    pub async fn process_event(&self, event: &Event<'_>) -> Result<(), types::Error> {
+        // block requests carry the device of the request, not the one of the filesystem
+        if !matches!(
+            event.event_type,
+            EventType::BlockRqRead | EventType::BlockRqWrite
+        ) {
+            self.path_by_inode
+                .entry((event.dev, event.inode))
+                .or_insert_with(async { Arc::from(event.path().as_ref()) })
+                .await;
+        }
+
+        match event.event_type {
+            EventType::VfsFsync | EventType::VfsFdatasync | EventType::SyncFileRange => {
+                let labels = self.get_labels(event).await;
+                metrics::counter!("fsync", labels.clone()).increment(1);
+                metrics::counter!("fsync_bytes", labels.clone()).increment(event.bytes);
+                metrics::histogram!("fsync_duration_seconds", labels)
+                    .record(event.duration().as_secs_f64());
+            }
+            EventType::VfsGetattr
+            | EventType::IterateDir
+            | EventType::VfsGetxattr
+            | EventType::VfsSetxattr => {
+                let labels = self.get_metadata_call_labels(event).await;
+                metrics::counter!("metadata_calls", labels).increment(event.weight());
+            }
+            EventType::NullPage => {
+                let labels = self.get_labels(event).await;
+                metrics::counter!("mmap_null_page", labels).increment(1);
+            }
+            // the read or write itself is already counted by the vfs events
+            EventType::NfsRead | EventType::NfsWrite => {
+                let labels = self.get_nfs_labels(event).await;
+                metrics::histogram!("nfs_duration_seconds", labels)
+                    .record(event.duration().as_secs_f64());
+            }
+            EventType::BlockRqRead | EventType::BlockRqWrite => {
+                let mut labels = self.get_block_rq_labels(event).await;
+                metrics::histogram!("block_rq_queue_seconds", labels.clone())
+                    .record(event.queue_duration().as_secs_f64());
+                metrics::histogram!("block_rq_duration_seconds", labels.clone())
+                    .record(event.duration().as_secs_f64());
+
+                labels.push(Label::new("path", event.path().to_string()));
+                labels.push(Label::new("cmd", self.get_cmd(event).await));
+                metrics::counter!("block_io_bytes", labels).increment(event.bytes);
+            }
+            _ => {
+                let mut labels = self.get_labels(event).await;
+                metrics::counter!("io", labels.clone()).increment(event.bytes);
+                // not on io, where it would triple the number of series
+                if event.pattern != AccessPattern::Unknown {
+                    labels.push(Label::new("pattern", event.pattern()));
+                    metrics::histogram!("io_request_bytes", labels).record(event.bytes as f64);
+                }
+            }
+        }
         Ok(())
     }
 
-    async fn get_labels(&self, event: &FileAccessEvent) -> Vec<Label> {
+    /// Rename targets are only logged, as a label they'd make a series per renamed file (every
+    /// temporary file written and renamed into place).
+    pub async fn process_meta_event(&self, meta: &MetaOp<'_>) -> Result<(), types::Error> {
+        let labels = self.get_labels(&meta.event).await;
+        metrics::counter!("meta_ops", labels).increment(1);
+        if !meta.new_path.is_empty() {
+            debug!("{} renamed to {}", meta.event.path(), meta.new_path);
+        }
+        Ok(())
+    }
+
+    pub async fn process_page_cache_delta(&self, key: &PageCacheKey, delta: &PageCacheStats) {
+        let delta = PageCacheDelta::from(*delta);
+        let path = self
+            .path_by_inode
+            .get(&(key.dev, key.inode))
+            .await
+            .unwrap_or_else(|| Arc::from(""));
+        let dev_name = self.get_device_name(key.dev).await;
+
+        for (result, pages) in [
+            ("hit", delta.hits),
+            ("miss", delta.misses),
+            ("readahead", delta.readahead),
+        ] {
+            if pages == 0 {
+                continue;
+            }
+            let labels = vec![
+                Label::new("path", path.to_string()),
+                Label::new("inode", key.inode.to_string()),
+                Label::new("dev_name", dev_name.clone()),
+                Label::new("result", result),
+                Label::new("ips", self.machine_info.string_ips.as_ref().to_owned()),
+                Label::new("hostname", self.machine_info.hostname.to_owned()),
+                Label::new("machine_id", self.machine_info.id.to_owned()),
+            ];
+            metrics::counter!("page_cache", labels).increment(pages);
+        }
+    }
+
+    pub async fn process_writeback_delta(&self, key: &WritebackKey, delta: &WritebackStats) {
+        let path = self
+            .path_by_inode
+            .get(&(key.dev, key.inode))
+            .await
+            .unwrap_or_else(|| Arc::from(""));
+        let dev_name = self.get_device_name(key.dev).await;
+        let cgroup = self.get_cgroup_path(key.cgroup_id).await;
+
+        for (stage, bytes) in [
+            ("dirtied", delta.dirtied_bytes),
+            ("written", delta.written_bytes),
+        ] {
+            if bytes == 0 {
+                continue;
+            }
+            let mut labels = vec![
+                Label::new("path", path.to_string()),
+                Label::new("inode", key.inode.to_string()),
+                Label::new("dev_name", dev_name.clone()),
+                Label::new("cgroup", cgroup.to_string()),
+                Label::new("stage", stage),
+                Label::new("ips", self.machine_info.string_ips.as_ref().to_owned()),
+                Label::new("hostname", self.machine_info.hostname.to_owned()),
+                Label::new("machine_id", self.machine_info.id.to_owned()),
+            ];
+            if self.optional_labels.unit {
+                let unit = cgroup::systemd_unit(&cgroup).to_string();
+                labels.push(Label::new("unit", unit));
+            }
+            metrics::counter!("writeback", labels).increment(bytes);
+        }
+    }
+
+    async fn get_labels(&self, event: &Event<'_>) -> Vec<Label> {
         let path = event.path();
         let cmd = self.get_cmd(event).await;
-        let dev_name = self.get_device_name(event).await;
-        let fs_type = self.get_fs_type(event).await;
+        let dev_name = self.get_device_name(event.dev).await;
+        let fs_type = self.get_fs_type(event.s_magic).await;
         let file_type = self.get_file_type(event).await;
         let perms = event.perms();
-        [
+        let mount = self.get_mount(event);
+        let parents = if self.optional_labels.parents {
+            let ancestry = self.get_ancestry(event).await;
+            let parent_cmd = ancestry.parent().unwrap_or_default().to_string();
+            // started straight from a shell or a supervisor, the caller is the top-level command
+            let root_cmd = ancestry.root().unwrap_or(&cmd).to_string();
+            Some((parent_cmd, root_cmd))
+        } else {
+            None
+        };
+        let labels = [
             Label::new("path", path.to_string()),
             Label::new("cmd", cmd),
             Label::new("dev_name", dev_name),
@@ -69,15 +320,158 @@ impl Aggregator {
             Label::new("syscall", event.syscall()),
             Label::new("direction", event.direction()),
             Label::new("type_name", event.type_name()),
+            Label::new("direct", event.is_direct().to_string()),
+            Label::new("fault_kind", event.fault_kind()),
+            Label::new("path_status", event.path_status()),
             // todo: to_owned :(
             Label::new("ips", self.machine_info.string_ips.as_ref().to_owned()),
             Label::new("hostname", self.machine_info.hostname.to_owned()),
             Label::new("machine_id", self.machine_info.id.to_owned()),
+        ];
+        let mut labels = Vec::from(labels);
+        if let Some(thread) = self.get_thread_name(event).await {
+            labels.push(Label::new("thread", thread));
+        }
+        if let Some((parent_cmd, root_cmd)) = parents {
+            labels.extend([
+                Label::new("parent_cmd", parent_cmd),
+                Label::new("root_cmd", root_cmd),
+            ]);
+        }
+        if self.optional_labels.ancestry {
+            let ancestry = self.get_ancestry(event).await;
+            labels.push(Label::new("ancestry", ancestry.chain()));
+        }
+        if self.optional_labels.unit {
+            let cgroup = self.get_cgroup_path(event.cgroup_id).await;
+            let unit = cgroup::systemd_unit(&cgroup).to_string();
+            labels.push(Label::new("unit", unit));
+        }
+        if self.optional_labels.users {
+            labels.extend([
+                Label::new("user", self.get_user_name(event.uid).await),
+                Label::new("group", self.get_group_name(event.gid).await),
+                Label::new("owner", self.get_user_name(event.i_uid).await),
+                Label::new("owner_group", self.get_group_name(event.i_gid).await),
+            ]);
+        }
+        if self.optional_labels.mnt_ns {
+            labels.push(Label::new("mnt_ns", event.mnt_ns.to_string()));
+        }
+        if self.optional_labels.mount {
+            let (mount_point, mount_source, mount_options) = match mount.as_deref() {
+                Some(mount) => (
+                    mount.mount_point.clone(),
+                    mount.source.clone(),
+                    mount.options.clone(),
+                ),
+                None => Default::default(),
+            };
+            labels.extend([
+                Label::new("mount_point", mount_point),
+                Label::new("mount_source", mount_source),
+                Label::new("mount_options", mount_options),
+            ]);
+        }
+        if self.optional_labels.device {
+            let topology = self.get_topology(event.dev).await;
+            labels.extend([
+                Label::new("dev_path", topology.path()),
+                Label::new("logical_volume", topology.logical_volume.clone()),
+                Label::new("physical_disk", topology.physical_disks()),
+            ]);
+        }
+        if self.optional_labels.backing {
+            let layer_dir = mount
+                .as_deref()
+                .and_then(|mount| mount.overlay_layer_dir(event.ovl_layer))
+                .unwrap_or_default()
+                .to_string();
+            let backing_fs_type = match event.real_s_magic {
+                0 => Arc::from(""),
+                magic => self.get_fs_type(magic).await,
+            };
+            let subvol = self.get_btrfs_subvol(event).await;
+            labels.extend([
+                Label::new("ovl_layer", event.ovl_layer()),
+                Label::new("layer_dir", layer_dir),
+                Label::new("backing_fs_type", backing_fs_type),
+                Label::new(
+                    "btrfs_subvol_id",
+                    match subvol.id {
+                        0 => String::new(),
+                        id => id.to_string(),
+                    },
+                ),
+                Label::new("btrfs_subvol", subvol.name.clone()),
+            ]);
+        }
+        if self.optional_labels.remote {
+            let (remote_host, remote_export) = self.get_remote(event, mount.as_deref());
+            labels.extend([
+                Label::new("remote_host", remote_host),
+                Label::new("remote_export", remote_export),
+            ]);
+        }
+        labels
+    }
+
+    async fn get_block_rq_labels(&self, event: &Event<'_>) -> Vec<Label> {
+        let dev_name = self.get_device_name(event.dev).await;
+        let topology = self.get_topology(event.dev).await;
+        [
+            Label::new("dev_name", dev_name),
+            Label::new("dev_path", topology.path()),
+            Label::new("logical_volume", topology.logical_volume.clone()),
+            Label::new("physical_disk", topology.physical_disks()),
+            Label::new("direction", event.direction()),
+            Label::new("status", event.rq_status()),
+            Label::new("ips", self.machine_info.string_ips.as_ref().to_owned()),
+            Label::new("hostname", self.machine_info.hostname.to_owned()),
+            Label::new("machine_id", self.machine_info.id.to_owned()),
         ]
         .into()
     }
 
-    async fn get_file_type(&self, event: &FileAccessEvent) -> Arc<str> {
+    async fn get_nfs_labels(&self, event: &Event<'_>) -> Vec<Label> {
+        let cmd = self.get_cmd(event).await;
+        let (remote_host, remote_export) = self.get_remote(event, self.get_mount(event).as_deref());
+        [
+            Label::new("remote_host", remote_host),
+            Label::new("remote_export", remote_export),
+            Label::new("cmd", cmd),
+            Label::new("direction", event.direction()),
+            Label::new("ips", self.machine_info.string_ips.as_ref().to_owned()),
+            Label::new("hostname", self.machine_info.hostname.to_owned()),
+            Label::new("machine_id", self.machine_info.id.to_owned()),
+        ]
+        .into()
+    }
+
+    /// Metadata calls come in storms, so they are only labelled by directory and process
+    /// rather than by the full path.
+    async fn get_metadata_call_labels(&self, event: &Event<'_>) -> Vec<Label> {
+        let cmd = self.get_cmd(event).await;
+        let dev_name = self.get_device_name(event.dev).await;
+        let fs_type = self.get_fs_type(event.s_magic).await;
+        let labels = [
+            Label::new("dir", event.dir()),
+            Label::new("cmd", cmd),
+            Label::new("dev_name", dev_name),
+            Label::new("fs_type", fs_type),
+            Label::new("syscall", event.syscall()),
+            Label::new("ips", self.machine_info.string_ips.as_ref().to_owned()),
+            Label::new("hostname", self.machine_info.hostname.to_owned()),
+            Label::new("machine_id", self.machine_info.id.to_owned()),
+        ];
+        let mut labels = Vec::from(labels);
+        if let Some(thread) = self.get_thread_name(event).await {
+            labels.push(Label::new("thread", thread));
+        }
+        labels
+    }
+
+    async fn get_file_type(&self, event: &Event<'_>) -> Arc<str> {
         self.file_type_by_mode
             .entry(event.file_type_mode())
             .or_insert_with(async {
@@ -92,11 +486,11 @@ impl Aggregator {
             .clone()
     }
 
-    async fn get_fs_type(&self, event: &FileAccessEvent) -> Arc<str> {
+    async fn get_fs_type(&self, magic: u64) -> Arc<str> {
         self.fs_type_by_magic
-            .entry(event.s_magic)
+            .entry(magic)
             .or_insert_with(async {
-                match event.fs_type() {
+                match FsType::from_magic(magic) {
                     Ok(fs_type) => Arc::from(fs_type.as_ref().to_owned()),
                     Err(types::Error::FsType(err)) => Arc::from(format!("{}", err.number)),
                     _ => unreachable!(),
@@ -107,13 +501,101 @@ impl Aggregator {
             .clone()
     }
 
-    async fn get_device_name(&self, event: &FileAccessEvent) -> Arc<str> {
+    /// Subvolume of the event's directory on btrfs, id 0 elsewhere and when it can't be found.
+    async fn get_btrfs_subvol(&self, event: &Event<'_>) -> Arc<Subvol> {
+        if !matches!(event.fs_type(), Ok(FsType::Btrfs)) {
+            return Arc::default();
+        }
+        self.btrfs_subvol_by_dir
+            .entry((event.dev, event.dir()))
+            .or_insert_with(async {
+                // paths of other mount namespaces may not exist here
+                Arc::new(Subvol::of_dir(&event.dir()).await.unwrap_or_default())
+            })
+            .await
+            .value()
+            .clone()
+    }
+
+    /// Falls back to the number for ids without a name, e.g. from a container's own passwd.
+    async fn get_user_name(&self, uid: u32) -> Arc<str> {
+        self.user_name_by_uid
+            .entry(uid)
+            .or_insert_with(async {
+                match Uid(uid).name().await {
+                    Ok(name) => Arc::from(name),
+                    Err(_err) => Arc::from(uid.to_string()),
+                }
+            })
+            .await
+            .value()
+            .clone()
+    }
+
+    async fn get_group_name(&self, gid: u32) -> Arc<str> {
+        self.group_name_by_gid
+            .entry(gid)
+            .or_insert_with(async {
+                match Gid(gid).name().await {
+                    Ok(name) => Arc::from(name),
+                    Err(_err) => Arc::from(gid.to_string()),
+                }
+            })
+            .await
+            .value()
+            .clone()
+    }
+
+    async fn get_cgroup_path(&self, id: u64) -> Arc<str> {
+        // flusher writeback of inodes not attached to a cgroup
+        if id == 0 {
+            return Arc::from("");
+        }
+        self.cgroup_path_by_id
+            .entry(id)
+            .or_insert_with(async {
+                match CgroupId(id).path().await {
+                    Ok(path) => Arc::from(path),
+                    Err(_err) => Arc::from(id.to_string()),
+                }
+            })
+            .await
+            .value()
+            .clone()
+    }
+
+    fn get_mount(&self, event: &Event<'_>) -> Option<Arc<Mount>> {
+        let mounts = self.mounts.read().unwrap().clone();
+        mounts.lookup(Dev(event.dev), &event.path)
+    }
+
+    /// Server and export of network filesystems, empty for local ones.
+    fn get_remote(&self, event: &Event<'_>, mount: Option<&Mount>) -> (String, String) {
+        if !matches!(
+            event.fs_type(),
+            Ok(FsType::Nfs
+                | FsType::Cifs
+                | FsType::Smb
+                | FsType::Smb2
+                | FsType::Ceph
+                | FsType::Fuse)
+        ) {
+            return Default::default();
+        }
+        match mount.and_then(Mount::remote) {
+            Some((host, export)) => (host.to_string(), export.to_string()),
+            None => Default::default(),
+        }
+    }
+
+    async fn get_device_name(&self, dev: u32) -> Arc<str> {
         self.device_name_by_dev
-            .entry(event.dev)
+            .entry(dev)
             .or_insert_with(async {
-                match event.dev_name().await {
+                let dev = Dev(dev);
+                match dev.name().await {
                     Ok(name) => Arc::from(name),
-                    Err(_err) => Arc::from(format!("{}:{}", event.major(), event.minor())),
+                    Err(_err) => Arc::from(format!("{}:{}", dev.major(), dev.minor())),
                 }
             })
             .await
@@ -121,12 +603,48 @@ impl Aggregator {
             .clone()
     }
 
-    async fn get_cmd(&self, event: &FileAccessEvent) -> Arc<str> {
+    async fn get_topology(&self, dev: u32) -> Arc<Topology> {
+        self.topology_by_dev
+            .entry(dev)
+            .or_insert_with(async {
+                // not a block device (tmpfs, overlayfs, NFS, ...)
+                Arc::new(Dev(dev).topology().await.unwrap_or_default())
+            })
+            .await
+            .value()
+            .clone()
+    }
+
+    /// Keyed by start time as well, so a recycled tgid doesn't inherit a stale ancestry.
+    async fn get_ancestry(&self, event: &Event<'_>) -> Arc<Ancestry> {
+        self.ancestry_by_process
+            .entry((event.tgid, event.start_time))
+            .or_insert_with(async { Arc::new(Ancestry::from_parent(event.ppid).await) })
+            .await
+            .value()
+            .clone()
+    }
+
+    /// Name of the calling thread, `None` unless thread labels are enabled. Taken from the
+    /// event rather than `/proc/<tgid>/task/<tid>/comm`, short-lived threads are gone by now.
+    async fn get_thread_name(&self, event: &Event<'_>) -> Option<String> {
+        let max = self.max_thread_names?;
+        let names = self
+            .thread_names_by_process
+            .entry((event.tgid, event.start_time))
+            .or_insert_with(async { Arc::new(ThreadNames::new(max)) })
+            .await
+            .value()
+            .clone();
+        Some(names.label(&event.comm()))
+    }
+
+    async fn get_cmd(&self, event: &Event<'_>) -> Arc<str> {
         self.cmd_name_by_tgid
             .entry(event.tgid)
             .or_insert_with(async {
-                match event.cmdline().await {
-                    Ok(cmd) => Arc::from(cmd.name()),
+                match self.resolve_cmd(event).await {
+                    Ok(cmd) => Arc::from(cmd),
                     Err(_err) => Arc::from(event.comm()),
                 }
             })
@@ -134,4 +652,22 @@ impl Aggregator {
             .value()
             .clone()
     }
+
+    /// Kernel threads have neither a command line nor an executable and fall back to their comm.
+    async fn resolve_cmd(&self, event: &Event<'_>) -> Result<String, types::Error> {
+        match self.cmd_label {
+            CmdLabel::Argv0 => Ok(event.cmdline().await?.name().into_owned()),
+            CmdLabel::Exe => Pid(event.tgid).exe().await,
+            CmdLabel::ExeBasename => Ok(basename(&Pid(event.tgid).exe().await?).to_owned()),
+            CmdLabel::Script => {
+                let exe = Pid(event.tgid).exe().await?;
+                if is_interpreter(&exe, &self.interpreters) {
+                    if let Some(script) = event.cmdline().await?.script() {
+                        return Ok(script);
+                    }
+                }
+                Ok(basename(&exe).to_owned())
+            }
+        }
+    }
 }
@@ -0,0 +1,145 @@
+use fetra_common::{PageCacheStats, WritebackStats};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Counters the eBPF side only ever increments, kept per entry of a map.
+pub trait Counters: Copy + Default {
+    /// What the counters grew by since `previous`, `None` if any of them went backwards.
+    fn checked_sub(&self, previous: &Self) -> Option<Self>;
+
+    fn is_zero(&self) -> bool;
+}
+
+impl Counters for PageCacheStats {
+    fn checked_sub(&self, previous: &Self) -> Option<Self> {
+        Some(Self {
+            accessed: self.accessed.checked_sub(previous.accessed)?,
+            added: self.added.checked_sub(previous.added)?,
+            readahead: self.readahead.checked_sub(previous.readahead)?,
+        })
+    }
+
+    fn is_zero(&self) -> bool {
+        self.accessed == 0 && self.added == 0 && self.readahead == 0
+    }
+}
+
+impl Counters for WritebackStats {
+    fn checked_sub(&self, previous: &Self) -> Option<Self> {
+        Some(Self {
+            dirtied_bytes: self.dirtied_bytes.checked_sub(previous.dirtied_bytes)?,
+            written_bytes: self.written_bytes.checked_sub(previous.written_bytes)?,
+        })
+    }
+
+    fn is_zero(&self) -> bool {
+        self.dirtied_bytes == 0 && self.written_bytes == 0
+    }
+}
+
+/// Turns the ever-growing counters of a stats map (`PAGE_CACHE_STATS`, `WRITEBACK_STATS`) into
+/// increments.
+#[derive(Debug)]
+pub struct CounterDeltas<K, S> {
+    previous: HashMap<K, S>,
+    current: HashMap<K, S>,
+}
+
+impl<K, S> Default for CounterDeltas<K, S> {
+    fn default() -> Self {
+        Self {
+            previous: HashMap::new(),
+            current: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash, S: Counters> CounterDeltas<K, S> {
+    pub fn update(&mut self, key: K, stats: S) -> Option<S> {
+        self.current.insert(key, stats);
+
+        let previous = self.previous.get(&key).copied().unwrap_or_default();
+        // the entry was evicted from the LRU map and created again
+        let delta = stats.checked_sub(&previous).unwrap_or(stats);
+        (!delta.is_zero()).then_some(delta)
+    }
+
+    /// Must be called after each full pass over the map, drops entries that are gone from it.
+    pub fn finish(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fetra_common::WritebackKey;
+
+    const KEY: WritebackKey = WritebackKey::new(0x0fd0_0003, 1234, 5678);
+
+    fn stats(dirtied_bytes: u64, written_bytes: u64) -> WritebackStats {
+        WritebackStats {
+            dirtied_bytes,
+            written_bytes,
+        }
+    }
+
+    fn delta(delta: Option<WritebackStats>) -> (u64, u64) {
+        let delta = delta.unwrap();
+        (delta.dirtied_bytes, delta.written_bytes)
+    }
+
+    #[test]
+    fn deltas_between_passes() {
+        let mut deltas = CounterDeltas::default();
+        assert_eq!(delta(deltas.update(KEY, stats(8192, 0))), (8192, 0));
+        deltas.finish();
+
+        assert_eq!(delta(deltas.update(KEY, stats(12288, 8192))), (4096, 8192));
+        deltas.finish();
+
+        assert!(deltas.update(KEY, stats(12288, 8192)).is_none());
+    }
+
+    #[test]
+    fn keys_apart() {
+        let other = WritebackKey::new(0x0fd0_0003, 1234, 9999);
+        let mut deltas = CounterDeltas::default();
+        deltas.update(KEY, stats(4096, 0));
+        deltas.update(other, stats(8192, 0));
+        deltas.finish();
+
+        assert_eq!(delta(deltas.update(other, stats(8192, 8192))), (0, 8192));
+    }
+
+    #[test]
+    fn entries_gone_from_the_map() {
+        let mut deltas = CounterDeltas::default();
+        deltas.update(KEY, stats(8192, 8192));
+        deltas.finish();
+        // not seen in this pass
+        deltas.finish();
+
+        assert_eq!(delta(deltas.update(KEY, stats(4096, 0))), (4096, 0));
+    }
+
+    #[test]
+    fn counters_going_backwards() {
+        // evicted and recreated between two passes, or wrapped around: counted from zero, a
+        // wrapped counter can't be told apart from a recreated entry
+        let mut deltas = CounterDeltas::default();
+        deltas.update(KEY, stats(u64::MAX - 4095, 4096));
+        deltas.finish();
+
+        assert_eq!(delta(deltas.update(KEY, stats(4096, 8192))), (4096, 8192));
+    }
+
+    #[test]
+    fn one_counter_going_backwards() {
+        let mut deltas = CounterDeltas::default();
+        deltas.update(KEY, stats(8192, 4096));
+        deltas.finish();
+
+        assert_eq!(delta(deltas.update(KEY, stats(12288, 0))), (12288, 0));
+    }
+}
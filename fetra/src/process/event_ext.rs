@@ -1,37 +1,67 @@
-use crate::types;
+use crate::process::record::Event;
 use crate::types::fs_type::FsType;
 use crate::types::mode::{FileType, Permissions};
 use crate::types::Result;
-use fetra_common::{EventType, FileAccessEvent};
+use fetra_common::{
+    AccessPattern, EventType, PathStatus, EVENT_FLAG_DIRECT, EVENT_FLAG_FAULT_ERROR,
+    EVENT_FLAG_FAULT_MAJOR, EVENT_FLAG_FAULT_MKWRITE, EVENT_FLAG_FAULT_RETRY,
+    EVENT_FLAG_FAULT_WRITE, EVENT_FLAG_RQ_ERROR,
+};
 use linux_raw_sys::general::S_IFMT;
 use std::borrow::Cow;
 use std::ffi::{c_char, CStr};
+use std::time::Duration;
 
 pub trait EventExt {
     fn comm(&self) -> Cow<str>;
     fn path(&self) -> Cow<str>;
+    fn dir(&self) -> String;
     async fn cmdline(&self) -> Result<Cmdline>;
 
-    fn major(&self) -> u32;
-    fn minor(&self) -> u32;
     fn file_type_mode(&self) -> u32;
 
     fn file_type(&self) -> Result<FileType>;
     fn perms(&self) -> Permissions;
     fn fs_type(&self) -> Result<FsType>;
-    async fn dev_name(&self) -> Result<String>;
     fn type_name(&self) -> &'static str;
     fn direction(&self) -> &'static str;
     fn syscall(&self) -> &'static str;
+    fn duration(&self) -> Duration;
+    fn queue_duration(&self) -> Duration;
+    fn weight(&self) -> u64;
+    fn is_direct(&self) -> bool;
+    fn fault_kind(&self) -> &'static str;
+    fn rq_status(&self) -> &'static str;
+    fn path_status(&self) -> &'static str;
+    fn pattern(&self) -> &'static str;
+    fn ovl_layer(&self) -> String;
 }
 
-impl EventExt for FileAccessEvent {
+impl EventExt for Event<'_> {
     fn comm(&self) -> Cow<str> {
         unsafe { CStr::from_ptr(self.comm.as_ptr() as *const c_char) }.to_string_lossy()
     }
 
+    /// Deleted files are suffixed like in `/proc/<pid>/fd`, so they stand out from the file
+    /// that may have taken their place.
     fn path(&self) -> Cow<str> {
-        unsafe { CStr::from_ptr(self.path.as_ptr() as *const c_char) }.to_string_lossy()
+        match self.path_status {
+            PathStatus::Deleted => Cow::Owned(format!("{} (deleted)", self.path)),
+            _ => Cow::Borrowed(&self.path),
+        }
+    }
+
+    fn dir(&self) -> String {
+        let path = self.path();
+        if let EventType::IterateDir = self.event_type {
+            return path.into_owned();
+        }
+
+        match path.rfind('/') {
+            Some(0) => String::from("/"),
+            Some(idx) => path[..idx].to_owned(),
+            None => path.into_owned(),
+        }
     }
 
     async fn cmdline(&self) -> Result<Cmdline> {
@@ -40,14 +70,6 @@ impl EventExt for FileAccessEvent {
         Ok(Cmdline::new(cmdline))
     }
 
-    fn major(&self) -> u32 {
-        (self.dev >> 20) & 0xfff
-    }
-
-    fn minor(&self) -> u32 {
-        self.dev & 0xfffff
-    }
-
     fn file_type_mode(&self) -> u32 {
         (self.i_mode as u32) & S_IFMT
     }
@@ -61,26 +83,7 @@ impl EventExt for FileAccessEvent {
     }
 
     fn fs_type(&self) -> Result<FsType> {
-        let magic = u32::try_from(self.s_magic)?;
-        let fs_type = FsType::try_from(magic)?;
-
-        Ok(fs_type)
-    }
-
-    async fn dev_name(&self) -> Result<String> {
-        let major = self.major();
-        let minor = self.minor();
-        let path = format!("/sys/dev/block/{major}:{minor}/uevent");
-        let content = match tokio::fs::read_to_string(&path).await {
-            Ok(content) => content,
-            Err(err) => return Err(types::Error::Path(err, path)),
-        };
-        for line in content.lines() {
-            if let Some(name) = line.strip_prefix("DEVNAME=") {
-                return Ok(name.to_string());
-            }
-        }
-        Err(types::Error::DeviceNotFound(self.major(), self.minor()))
+        FsType::from_magic(self.s_magic)
     }
 
     fn type_name(&self) -> &'static str {
@@ -92,6 +95,29 @@ impl EventExt for FileAccessEvent {
             EventType::VfsWrite => "vfs",
             EventType::VfsReadv => "vfs",
             EventType::VfsWritev => "vfs",
+            EventType::VfsFsync => "vfs",
+            EventType::VfsFdatasync => "vfs",
+            EventType::SyncFileRange => "vfs",
+            EventType::VfsUnlink => "meta",
+            EventType::VfsRename => "meta",
+            EventType::VfsMkdir => "meta",
+            EventType::VfsRmdir => "meta",
+            EventType::DoTruncate => "meta",
+            EventType::VfsFallocate => "meta",
+            EventType::VfsGetattr => "meta",
+            EventType::IterateDir => "meta",
+            EventType::VfsGetxattr => "meta",
+            EventType::VfsSetxattr => "meta",
+            EventType::IoUringRead => "io_uring",
+            EventType::IoUringWrite => "io_uring",
+            EventType::AioRead => "aio",
+            EventType::AioWrite => "aio",
+            EventType::VfsIocbRead => "vfs",
+            EventType::VfsIocbWrite => "vfs",
+            EventType::BlockRqRead => "block",
+            EventType::BlockRqWrite => "block",
+            EventType::NfsRead => "nfs",
+            EventType::NfsWrite => "nfs",
         }
     }
 
@@ -99,11 +125,35 @@ impl EventExt for FileAccessEvent {
         match self.event_type {
             EventType::MmapRead => "read",
             EventType::MmapWrite => "write",
-            EventType::NullPage => "unknown",
+            EventType::NullPage if self.flags & EVENT_FLAG_FAULT_WRITE != 0 => "write",
+            EventType::NullPage => "read",
             EventType::VfsRead => "read",
             EventType::VfsWrite => "write",
             EventType::VfsReadv => "read",
             EventType::VfsWritev => "write",
+            EventType::VfsFsync => "write",
+            EventType::VfsFdatasync => "write",
+            EventType::SyncFileRange => "write",
+            EventType::VfsUnlink => "write",
+            EventType::VfsRename => "write",
+            EventType::VfsMkdir => "write",
+            EventType::VfsRmdir => "write",
+            EventType::DoTruncate => "write",
+            EventType::VfsFallocate => "write",
+            EventType::VfsGetattr => "read",
+            EventType::IterateDir => "read",
+            EventType::VfsGetxattr => "read",
+            EventType::VfsSetxattr => "write",
+            EventType::IoUringRead => "read",
+            EventType::IoUringWrite => "write",
+            EventType::AioRead => "read",
+            EventType::AioWrite => "write",
+            EventType::VfsIocbRead => "read",
+            EventType::VfsIocbWrite => "write",
+            EventType::BlockRqRead => "read",
+            EventType::BlockRqWrite => "write",
+            EventType::NfsRead => "read",
+            EventType::NfsWrite => "write",
         }
     }
 
@@ -111,11 +161,117 @@ impl EventExt for FileAccessEvent {
         match self.event_type {
             EventType::MmapRead => "mmap",
             EventType::MmapWrite => "mmap",
-            EventType::NullPage => "unknown",
+            EventType::NullPage => "mmap",
             EventType::VfsRead => "vfs_read",
             EventType::VfsWrite => "vfs_write",
             EventType::VfsReadv => "vfs_readv",
             EventType::VfsWritev => "vfs_writev",
+            EventType::VfsFsync => "vfs_fsync",
+            EventType::VfsFdatasync => "vfs_fdatasync",
+            EventType::SyncFileRange => "sync_file_range",
+            EventType::VfsUnlink => "vfs_unlink",
+            EventType::VfsRename => "vfs_rename",
+            EventType::VfsMkdir => "vfs_mkdir",
+            EventType::VfsRmdir => "vfs_rmdir",
+            EventType::DoTruncate => "do_truncate",
+            EventType::VfsFallocate => "vfs_fallocate",
+            EventType::VfsGetattr => "vfs_getattr",
+            EventType::IterateDir => "iterate_dir",
+            EventType::VfsGetxattr => "vfs_getxattr",
+            EventType::VfsSetxattr => "vfs_setxattr",
+            EventType::IoUringRead => "io_uring_read",
+            EventType::IoUringWrite => "io_uring_write",
+            EventType::AioRead => "aio_read",
+            EventType::AioWrite => "aio_write",
+            EventType::VfsIocbRead => "vfs_iocb_iter_read",
+            EventType::VfsIocbWrite => "vfs_iocb_iter_write",
+            EventType::BlockRqRead => "block_rq_read",
+            EventType::BlockRqWrite => "block_rq_write",
+            EventType::NfsRead => "nfs_file_read",
+            EventType::NfsWrite => "nfs_file_write",
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        Duration::from_nanos(self.duration_ns)
+    }
+
+    fn queue_duration(&self) -> Duration {
+        Duration::from_nanos(self.queue_ns)
+    }
+
+    fn weight(&self) -> u64 {
+        self.weight.max(1) as u64
+    }
+
+    fn is_direct(&self) -> bool {
+        self.flags & EVENT_FLAG_DIRECT != 0
+    }
+
+    fn path_status(&self) -> &'static str {
+        match self.path_status {
+            PathStatus::Unresolved => "unresolved",
+            PathStatus::Complete => "complete",
+            PathStatus::Truncated => "truncated",
+            PathStatus::Deleted => "deleted",
+            PathStatus::Anonymous => "anonymous",
+        }
+    }
+
+    /// Empty for everything but vfs reads and writes of files.
+    fn pattern(&self) -> &'static str {
+        match self.pattern {
+            AccessPattern::Unknown => "",
+            AccessPattern::Sequential => "sequential",
+            AccessPattern::Random => "random",
+            AccessPattern::Rewind => "rewind",
+        }
+    }
+
+    /// `upper` or `lower<n>`, counting lower dirs from 1 like overlayfs does.
+    fn ovl_layer(&self) -> String {
+        match self.ovl_layer {
+            0 => String::new(),
+            1 => String::from("upper"),
+            layer => format!("lower{}", layer - 1),
+        }
+    }
+
+    /// Outcome of an mmap fault, empty for everything else.
+    fn fault_kind(&self) -> &'static str {
+        if !matches!(
+            self.event_type,
+            EventType::MmapRead | EventType::MmapWrite | EventType::NullPage
+        ) {
+            return "";
+        }
+
+        if self.flags & EVENT_FLAG_FAULT_ERROR != 0 {
+            "error"
+        } else if self.flags & EVENT_FLAG_FAULT_RETRY != 0 {
+            "retry"
+        } else if self.flags & EVENT_FLAG_FAULT_MKWRITE != 0 {
+            "mkwrite"
+        } else if self.flags & EVENT_FLAG_FAULT_MAJOR != 0 {
+            "major"
+        } else {
+            "minor"
+        }
+    }
+
+    /// Outcome of a block request, empty for everything else.
+    fn rq_status(&self) -> &'static str {
+        if !matches!(
+            self.event_type,
+            EventType::BlockRqRead | EventType::BlockRqWrite
+        ) {
+            return "";
+        }
+
+        if self.flags & EVENT_FLAG_RQ_ERROR != 0 {
+            "error"
+        } else {
+            "ok"
         }
     }
 }
@@ -142,4 +298,162 @@ impl Cmdline {
         let name = String::from_utf8_lossy(&self.data[..self.name_end_index]);
         name
     }
+
+    /// File name of the script or module an interpreter runs: `manage.py` for
+    /// `python3 -u /srv/app/manage.py runserver`, `celery` for `python -m celery worker`.
+    /// `None` for inline code and an interactive interpreter.
+    pub fn script(&self) -> Option<String> {
+        let mut args = self.data.split(|c| *c == 0);
+        let interpreter = String::from_utf8_lossy(args.next()?);
+        let options_with_value = options_with_value(&interpreter);
+        let mut args = args
+            .filter(|arg| !arg.is_empty())
+            .map(String::from_utf8_lossy);
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                option if options_with_value.contains(&option) => {
+                    args.next();
+                }
+                "-m" => return args.next().map(Cow::into_owned),
+                "-c" | "-e" => return None,
+                option if option.starts_with('-') => continue,
+                script => return Some(basename(script).to_owned()),
+            }
+        }
+        None
+    }
+}
+
+/// Options of `interpreter` that take the next argument as their value, as in `python3 -W ignore`.
+fn options_with_value(interpreter: &str) -> &'static [&'static str] {
+    let name = basename(interpreter).trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    match name {
+        "python" => &["-W", "-X", "--check-hash-based-pycs"],
+        "ruby" => &["-I", "-r", "-C", "-E"],
+        "node" => &["-r", "--require", "--import", "--loader"],
+        "php" => &["-c", "-d", "-z"],
+        "bash" | "sh" | "dash" | "zsh" => &["-o", "-O", "--rcfile", "--init-file"],
+        _ => &[],
+    }
+}
+
+/// Whether `exe` is one of `interpreters`, versioned ones included: `python3.12` is `python`.
+pub fn is_interpreter(exe: &str, interpreters: &[String]) -> bool {
+    interpreters.iter().any(|interpreter| {
+        basename(exe)
+            .strip_prefix(interpreter.as_str())
+            .is_some_and(|version| version.chars().all(|c| c.is_ascii_digit() || c == '.'))
+    })
+}
+
+pub fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(args: &[&str]) -> Option<String> {
+        let mut data = args.join("\0").into_bytes();
+        data.push(0);
+        Cmdline::new(data).script()
+    }
+
+    #[test]
+    fn cmdline_name() {
+        let cmdline = Cmdline::new(b"/usr/bin/python3\0-u\0manage.py\0".to_vec());
+        assert_eq!(cmdline.name(), "/usr/bin/python3");
+    }
+
+    #[test]
+    fn script_of_interpreters() {
+        assert_eq!(
+            script(&["python3", "-u", "/srv/app/manage.py", "runserver"]).as_deref(),
+            Some("manage.py")
+        );
+        assert_eq!(
+            script(&["/usr/bin/perl", "-w", "/usr/sbin/logwatch"]).as_deref(),
+            Some("logwatch")
+        );
+        assert_eq!(
+            script(&["node", "--max-old-space-size=4096", "dist/server.js"]).as_deref(),
+            Some("server.js")
+        );
+        assert_eq!(
+            script(&["/bin/bash", "./backup.sh", "--full"]).as_deref(),
+            Some("backup.sh")
+        );
+    }
+
+    #[test]
+    fn script_of_modules_and_inline_code() {
+        assert_eq!(
+            script(&["python", "-m", "celery", "worker"]).as_deref(),
+            Some("celery")
+        );
+        assert_eq!(script(&["python3", "-c", "print(1)"]), None);
+        assert_eq!(script(&["perl", "-e", "print 1"]), None);
+        assert_eq!(script(&["ruby", "-e", "puts 1"]), None);
+        // interactive
+        assert_eq!(script(&["python3"]), None);
+        assert_eq!(script(&["python3", "-i"]), None);
+    }
+
+    #[test]
+    fn script_after_options_with_values() {
+        assert_eq!(
+            script(&["python3", "-W", "ignore", "-X", "importtime", "job.py"]).as_deref(),
+            Some("job.py")
+        );
+        assert_eq!(
+            script(&["python3.12", "-O", "job.py"]).as_deref(),
+            Some("job.py")
+        );
+        assert_eq!(
+            script(&["node", "-r", "dotenv/config", "server.js"]).as_deref(),
+            Some("server.js")
+        );
+        assert_eq!(
+            script(&["ruby", "-I", "lib", "-r", "json", "bin/rake"]).as_deref(),
+            Some("rake")
+        );
+        assert_eq!(
+            script(&["bash", "-o", "pipefail", "deploy.sh"]).as_deref(),
+            Some("deploy.sh")
+        );
+        assert_eq!(
+            script(&["php", "-c", "/etc/php.ini", "artisan", "queue:work"]).as_deref(),
+            Some("artisan")
+        );
+        assert_eq!(script(&["python3", "-W", "ignore"]), None);
+    }
+
+    #[test]
+    fn interpreters() {
+        let interpreters: Vec<String> = ["python", "perl", "node", "sh"]
+            .iter()
+            .map(|interpreter| interpreter.to_string())
+            .collect();
+        assert!(is_interpreter("/usr/bin/python3", &interpreters));
+        assert!(is_interpreter("/usr/bin/python3.12", &interpreters));
+        assert!(is_interpreter("/usr/bin/python", &interpreters));
+        assert!(is_interpreter("/usr/bin/perl5.36.0", &interpreters));
+        assert!(is_interpreter("/usr/bin/sh", &interpreters));
+        assert!(is_interpreter("node", &interpreters));
+        // other programs sharing a prefix
+        assert!(!is_interpreter("/usr/bin/python3-config", &interpreters));
+        assert!(!is_interpreter("/usr/bin/shred", &interpreters));
+        assert!(!is_interpreter("/usr/bin/nodejs-wrapper", &interpreters));
+        assert!(!is_interpreter("/usr/bin/postgres", &interpreters));
+        assert!(!is_interpreter("/usr/bin/python3", &[]));
+    }
+
+    #[test]
+    fn basenames() {
+        assert_eq!(basename("/usr/bin/python3"), "python3");
+        assert_eq!(basename("python3"), "python3");
+        assert_eq!(basename("./backup.sh"), "backup.sh");
+        assert_eq!(basename(""), "");
+    }
 }
@@ -1,2 +1,5 @@
 pub mod aggregator;
+pub mod counters;
 pub mod event_ext;
+pub mod page_cache;
+pub mod record;
@@ -0,0 +1,45 @@
+use fetra_common::PageCacheStats;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PageCacheDelta {
+    pub hits: u64,
+    pub misses: u64,
+    pub readahead: u64,
+}
+
+/// Hits are derived the same way `cachestat` does it: pages accessed minus pages that had to be
+/// added to the page cache.
+impl From<PageCacheStats> for PageCacheDelta {
+    fn from(stats: PageCacheStats) -> Self {
+        Self {
+            hits: stats.accessed.saturating_sub(stats.added),
+            misses: stats.added,
+            readahead: stats.readahead,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(accessed: u64, added: u64, readahead: u64) -> (u64, u64, u64) {
+        let delta = PageCacheDelta::from(PageCacheStats {
+            accessed,
+            added,
+            readahead,
+        });
+        (delta.hits, delta.misses, delta.readahead)
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        assert_eq!(delta(10, 4, 8), (6, 4, 8));
+    }
+
+    #[test]
+    fn more_added_than_accessed() {
+        // readahead adds pages nobody accessed yet
+        assert_eq!(delta(2, 32, 32), (0, 32, 32));
+    }
+}
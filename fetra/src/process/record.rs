@@ -0,0 +1,68 @@
+use crate::types;
+use crate::types::Result;
+use bytemuck::Pod;
+use fetra_common::{FileAccessEvent, MetaOpEvent};
+use std::borrow::Cow;
+use std::mem::size_of;
+use std::ops::Deref;
+
+/// `EVENTS` record: a [`FileAccessEvent`] followed by its path.
+pub struct Event<'a> {
+    event: &'a FileAccessEvent,
+    pub path: Cow<'a, str>,
+}
+
+impl<'a> Event<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let (event, rest) = split_header::<FileAccessEvent>(data)?;
+        let (path, _) = split_path(rest, event.path_len)?;
+        Ok(Self { event, path })
+    }
+}
+
+impl Deref for Event<'_> {
+    type Target = FileAccessEvent;
+
+    fn deref(&self) -> &Self::Target {
+        self.event
+    }
+}
+
+/// `META_EVENTS` record: a [`MetaOpEvent`] followed by the affected and the new path.
+pub struct MetaOp<'a> {
+    pub event: Event<'a>,
+    pub new_path: Cow<'a, str>,
+}
+
+impl<'a> MetaOp<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let (meta, rest) = split_header::<MetaOpEvent>(data)?;
+        let (path, rest) = split_path(rest, meta.event.path_len)?;
+        let (new_path, _) = split_path(rest, meta.new_path_len)?;
+        Ok(Self {
+            event: Event {
+                event: &meta.event,
+                path,
+            },
+            new_path,
+        })
+    }
+}
+
+fn split_header<T: Pod>(data: &[u8]) -> Result<(&T, &[u8])> {
+    if data.len() < size_of::<T>() {
+        return Err(types::Error::Record(data.len()));
+    }
+    let (header, rest) = data.split_at(size_of::<T>());
+    let header = bytemuck::try_from_bytes(header).map_err(|_| types::Error::Record(data.len()))?;
+    Ok((header, rest))
+}
+
+fn split_path<'a>(data: &'a [u8], len: u16) -> Result<(Cow<'a, str>, &'a [u8])> {
+    let len = len as usize;
+    if data.len() < len {
+        return Err(types::Error::Record(data.len()));
+    }
+    let (path, rest) = data.split_at(len);
+    Ok((String::from_utf8_lossy(path), rest))
+}
@@ -0,0 +1,57 @@
+use crate::types;
+use crate::types::Result;
+use linux_raw_sys::btrfs::btrfs_ioctl_get_subvol_info_args;
+use linux_raw_sys::ioctl::BTRFS_IOC_GET_SUBVOL_INFO;
+use std::ffi::CStr;
+use std::fs::File;
+use std::os::fd::AsRawFd;
+
+/// Id of the top level subvolume, which has no name.
+const BTRFS_FS_TREE_OBJECTID: u64 = 5;
+
+/// Btrfs subvolume a directory belongs to.
+#[derive(Debug, Clone, Default)]
+pub struct Subvol {
+    pub id: u64,
+    pub name: String,
+}
+
+impl Subvol {
+    /// Asks btrfs which subvolume `dir` is in, doesn't need privileges unlike the tree search
+    /// ioctls.
+    pub async fn of_dir(dir: &str) -> Result<Self> {
+        let dir = dir.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let file = match File::open(&dir) {
+                Ok(file) => file,
+                Err(err) => return Err(types::Error::Path(err, dir)),
+            };
+
+            let mut args: btrfs_ioctl_get_subvol_info_args = unsafe { std::mem::zeroed() };
+            let ret = unsafe {
+                libc::ioctl(
+                    file.as_raw_fd(),
+                    BTRFS_IOC_GET_SUBVOL_INFO as _,
+                    &mut args as *mut btrfs_ioctl_get_subvol_info_args,
+                )
+            };
+            if ret < 0 {
+                return Err(types::Error::Path(std::io::Error::last_os_error(), dir));
+            }
+
+            let name = if args.treeid == BTRFS_FS_TREE_OBJECTID {
+                String::from("/")
+            } else {
+                unsafe { CStr::from_ptr(args.name.as_ptr()) }
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            Ok(Self {
+                id: args.treeid,
+                name,
+            })
+        })
+        .await
+        .map_err(std::io::Error::from)?
+    }
+}
@@ -0,0 +1,125 @@
+use crate::types;
+use crate::types::Result;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// cgroup v2 id, which is the inode number of the cgroup directory in cgroupfs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CgroupId(pub u64);
+
+impl CgroupId {
+    /// Path of the cgroup relative to the cgroupfs root, e.g. `/system.slice/foo.service`.
+    pub async fn path(&self) -> Result<String> {
+        // cgroups come and go while walking the tree, those gone are skipped
+        let mut pending = vec![PathBuf::from(CGROUP_ROOT)];
+        while let Some(dir) = pending.pop() {
+            let Ok(metadata) = tokio::fs::metadata(&dir).await else {
+                continue;
+            };
+            if metadata.ino() == self.0 {
+                let relative = dir.strip_prefix(CGROUP_ROOT).unwrap_or(&dir);
+                return Ok(format!("/{}", relative.display()));
+            }
+
+            let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+                continue;
+            };
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                if entry
+                    .file_type()
+                    .await
+                    .is_ok_and(|file_type| file_type.is_dir())
+                {
+                    pending.push(entry.path());
+                }
+            }
+        }
+        Err(types::Error::CgroupNotFound(self.0))
+    }
+}
+
+/// systemd unit a cgroup path belongs to, e.g. `postgresql.service` for
+/// `/system.slice/postgresql.service` and for cgroups the service created below its own. Units
+/// of a user manager belong to the manager's `user@1000.service`. Empty outside of services and
+/// scopes.
+pub fn systemd_unit(path: &str) -> &str {
+    let mut rest = path.trim_start_matches('/');
+    // slices only group units
+    while let Some((_slice, nested)) = rest
+        .split_once('/')
+        .filter(|(component, _)| component.ends_with(".slice"))
+    {
+        rest = nested;
+    }
+    let unit = rest.split('/').next().unwrap_or_default();
+    if unit.ends_with(".service") || unit.ends_with(".scope") {
+        unit
+    } else {
+        ""
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn services_and_scopes() {
+        assert_eq!(
+            systemd_unit("/system.slice/postgresql.service"),
+            "postgresql.service"
+        );
+        assert_eq!(systemd_unit("/init.scope"), "init.scope");
+        assert_eq!(
+            systemd_unit("/user.slice/user-1000.slice/session-3.scope"),
+            "session-3.scope"
+        );
+        assert_eq!(
+            systemd_unit("/system.slice/docker-4f1c2a.scope"),
+            "docker-4f1c2a.scope"
+        );
+        assert_eq!(
+            systemd_unit(
+                "/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod7d2e.slice/cri-containerd-9b3f.scope"
+            ),
+            "cri-containerd-9b3f.scope"
+        );
+    }
+
+    #[test]
+    fn user_manager_units() {
+        assert_eq!(
+            systemd_unit("/user.slice/user-1000.slice/user@1000.service/app.slice/foo.service"),
+            "user@1000.service"
+        );
+        assert_eq!(
+            systemd_unit("/user.slice/user-1000.slice/user@1000.service/init.scope"),
+            "user@1000.service"
+        );
+    }
+
+    #[test]
+    fn nested_cgroups() {
+        assert_eq!(
+            systemd_unit("/system.slice/postgresql.service/sub"),
+            "postgresql.service"
+        );
+        assert_eq!(
+            systemd_unit("/system.slice/docker-4f1c2a.scope/init/payload.scope"),
+            "docker-4f1c2a.scope"
+        );
+    }
+
+    #[test]
+    fn outside_units() {
+        assert_eq!(systemd_unit("/"), "");
+        assert_eq!(systemd_unit(""), "");
+        assert_eq!(systemd_unit("/system.slice"), "");
+        assert_eq!(systemd_unit("/user.slice/user-1000.slice"), "");
+        // cgroupfs driver, no systemd
+        assert_eq!(systemd_unit("/docker/4f1c2a"), "");
+        assert_eq!(systemd_unit("/kubepods/burstable/pod7d2e/9b3f"), "");
+    }
+}
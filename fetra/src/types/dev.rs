@@ -0,0 +1,230 @@
+use crate::types;
+use crate::types::Result;
+use std::path::{Path, PathBuf};
+
+/// Kernel-internal `dev_t` as found in `super_block::s_dev`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Dev(pub u32);
+
+impl Dev {
+    pub fn new(major: u32, minor: u32) -> Self {
+        Self((major << 20) | minor)
+    }
+
+    pub fn major(&self) -> u32 {
+        (self.0 >> 20) & 0xfff
+    }
+
+    pub fn minor(&self) -> u32 {
+        self.0 & 0xfffff
+    }
+
+    pub async fn name(&self) -> Result<String> {
+        let major = self.major();
+        let minor = self.minor();
+        let path = format!("/sys/dev/block/{major}:{minor}/uevent");
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(err) => return Err(types::Error::Path(err, path)),
+        };
+        for line in content.lines() {
+            if let Some(name) = line.strip_prefix("DEVNAME=") {
+                return Ok(name.to_string());
+            }
+        }
+        Err(types::Error::DeviceNotFound(major, minor))
+    }
+}
+
+const SYS_DEV_BLOCK: &str = "/sys/dev/block";
+
+/// Stacked block devices are rarely deeper than LV, RAID/crypt, partition, disk.
+const MAX_TOPOLOGY_DEPTH: usize = 8;
+
+/// Block devices below a device, from the logical volume down to the physical disks.
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    /// LVM `vg/lv` or the device-mapper name, empty for other devices.
+    pub logical_volume: String,
+    /// Kernel names per level, e.g. `[["dm-3"], ["nvme0n1p2"], ["nvme0n1"]]`, with several
+    /// devices on a level for RAID and striped volumes.
+    levels: Vec<Vec<String>>,
+}
+
+impl Topology {
+    /// `vg0/data -> dm-3 -> nvme0n1p2 -> nvme0n1`, devices of one level are joined with `+`.
+    pub fn path(&self) -> String {
+        let levels = self.levels.iter().map(|level| level.join("+"));
+        if self.logical_volume.is_empty() {
+            levels.collect::<Vec<_>>().join(" -> ")
+        } else {
+            std::iter::once(self.logical_volume.clone())
+                .chain(levels)
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        }
+    }
+
+    /// Disks at the bottom of the stack, comma separated.
+    pub fn physical_disks(&self) -> String {
+        self.levels
+            .last()
+            .map(|level| level.join(","))
+            .unwrap_or_default()
+    }
+}
+
+impl Dev {
+    /// Walks down the device stack in sysfs: `slaves/` of device-mapper and MD devices (the
+    /// reverse of the lower devices' `holders/`), and from partitions to their disk.
+    pub async fn topology(&self) -> Result<Topology> {
+        let major = self.major();
+        let minor = self.minor();
+        let path = format!("{SYS_DEV_BLOCK}/{major}:{minor}");
+        let dir = match tokio::fs::canonicalize(&path).await {
+            Ok(dir) => dir,
+            Err(err) => return Err(types::Error::Path(err, path)),
+        };
+
+        let logical_volume = match tokio::fs::read_to_string(dir.join("dm/name")).await {
+            Ok(name) => {
+                let name = name.trim();
+                let uuid = tokio::fs::read_to_string(dir.join("dm/uuid"))
+                    .await
+                    .unwrap_or_default();
+                if uuid.starts_with("LVM-") {
+                    lvm_name(name)
+                } else {
+                    name.to_string()
+                }
+            }
+            Err(_err) => String::new(),
+        };
+
+        let mut levels = Vec::new();
+        let mut current = vec![dir];
+        for _ in 0..MAX_TOPOLOGY_DEPTH {
+            if current.is_empty() {
+                break;
+            }
+            levels.push(
+                current
+                    .iter()
+                    .filter_map(|dir| dir.file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .collect(),
+            );
+
+            let mut lower = Vec::new();
+            for dir in &current {
+                lower.extend(lower_devices(dir).await);
+            }
+            lower.sort();
+            lower.dedup();
+            current = lower;
+        }
+
+        Ok(Topology {
+            logical_volume,
+            levels,
+        })
+    }
+}
+
+async fn lower_devices(dir: &Path) -> Vec<PathBuf> {
+    if tokio::fs::try_exists(dir.join("partition"))
+        .await
+        .unwrap_or(false)
+    {
+        return dir.parent().map(Path::to_path_buf).into_iter().collect();
+    }
+
+    let mut lower = Vec::new();
+    let Ok(mut entries) = tokio::fs::read_dir(dir.join("slaves")).await else {
+        return lower;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Ok(path) = tokio::fs::canonicalize(entry.path()).await {
+            lower.push(path);
+        }
+    }
+    lower
+}
+
+/// `my--vg-my--lv` to `my-vg/my-lv`, device-mapper doubles the dashes inside LVM names.
+fn lvm_name(dm_name: &str) -> String {
+    let mut name = String::with_capacity(dm_name.len());
+    let mut chars = dm_name.chars().peekable();
+    let mut split = false;
+    while let Some(c) = chars.next() {
+        if c != '-' {
+            name.push(c);
+        } else if chars.peek() == Some(&'-') {
+            chars.next();
+            name.push('-');
+        } else if !split {
+            split = true;
+            name.push('/');
+        } else {
+            // suffix of internal volumes, e.g. `-real` and `-cow` of snapshots
+            name.push('-');
+        }
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dev_numbers() {
+        let dev = Dev::new(259, 2);
+        assert_eq!(dev.major(), 259);
+        assert_eq!(dev.minor(), 2);
+        assert_eq!(Dev(0x0fd00003).major(), 253);
+        assert_eq!(Dev(0x0fd00003).minor(), 3);
+    }
+
+    #[test]
+    fn lvm_names() {
+        assert_eq!(lvm_name("vg0-root"), "vg0/root");
+        assert_eq!(lvm_name("my--vg-my--lv"), "my-vg/my-lv");
+        assert_eq!(lvm_name("ubuntu--vg-ubuntu--lv"), "ubuntu-vg/ubuntu-lv");
+        // snapshot internals
+        assert_eq!(lvm_name("vg0-snap-cow"), "vg0/snap-cow");
+        assert_eq!(lvm_name("vg0-data--1-real"), "vg0/data-1-real");
+        // thin pool internals
+        assert_eq!(lvm_name("vg0-pool_tdata"), "vg0/pool_tdata");
+    }
+
+    #[test]
+    fn topology_of_lv_on_partition() {
+        let topology = Topology {
+            logical_volume: "vg0/data".to_string(),
+            levels: vec![
+                vec!["dm-3".to_string()],
+                vec!["nvme0n1p2".to_string()],
+                vec!["nvme0n1".to_string()],
+            ],
+        };
+        assert_eq!(topology.path(), "vg0/data -> dm-3 -> nvme0n1p2 -> nvme0n1");
+        assert_eq!(topology.physical_disks(), "nvme0n1");
+    }
+
+    #[test]
+    fn topology_of_raid() {
+        let topology = Topology {
+            logical_volume: String::new(),
+            levels: vec![
+                vec!["md0".to_string()],
+                vec!["sda1".to_string(), "sdb1".to_string()],
+                vec!["sda".to_string(), "sdb".to_string()],
+            ],
+        };
+        assert_eq!(topology.path(), "md0 -> sda1+sdb1 -> sda+sdb");
+        assert_eq!(topology.physical_disks(), "sda,sdb");
+        assert_eq!(Topology::default().path(), "");
+        assert_eq!(Topology::default().physical_disks(), "");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::types::Result;
 use linux_raw_sys::general::*;
 use num_enum::TryFromPrimitive;
 use strum_macros::AsRefStr;
@@ -87,3 +88,9 @@ pub enum FsType {
     Secretmem = SECRETMEM_MAGIC,
     PidFs = PID_FS_MAGIC,
 }
+
+impl FsType {
+    pub fn from_magic(magic: u64) -> Result<Self> {
+        Ok(Self::try_from(u32::try_from(magic)?)?)
+    }
+}
@@ -3,8 +3,14 @@ use crate::types::mode::FileType;
 use num_enum::TryFromPrimitiveError;
 use std::num::TryFromIntError;
 
+pub mod btrfs;
+pub mod cgroup;
+pub mod dev;
 pub mod fs_type;
 pub mod mode;
+pub mod mount;
+pub mod process;
+pub mod user;
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Failed to read dir {0}")]
@@ -13,6 +19,21 @@ pub enum Error {
     #[error("Device {0}:{1} not found")]
     DeviceNotFound(u32, u32),
 
+    #[error("Malformed ring buffer record of {0} bytes")]
+    Record(usize),
+
+    #[error("Cgroup {0} not found")]
+    CgroupNotFound(u64),
+
+    #[error("Process {0} not found")]
+    ProcessNotFound(u32),
+
+    #[error("User {0} not found")]
+    UserNotFound(u32),
+
+    #[error("Group {0} not found")]
+    GroupNotFound(u32),
+
     #[error("{0}: {1}")]
     Path(std::io::Error, String),
 
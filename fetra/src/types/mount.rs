@@ -0,0 +1,308 @@
+use crate::types;
+use crate::types::dev::Dev;
+use crate::types::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+/// One line of `/proc/self/mountinfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub dev: Dev,
+    /// Directory of the filesystem mounted, `/` unless it's a bind mount.
+    pub root: String,
+    pub mount_point: String,
+    /// Per-mount options, e.g. `rw,relatime`.
+    pub options: String,
+    pub fs_type: String,
+    /// Device or remote export, e.g. `/dev/sda1`, `server:/export` or `overlay`.
+    pub source: String,
+    /// Filesystem options, e.g. the `lowerdir`/`upperdir` of an overlay.
+    pub super_options: String,
+}
+
+impl Mount {
+    /// `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue`
+    fn parse(line: &str) -> Option<Self> {
+        let (mount, fs) = line.split_once(" - ")?;
+
+        let mut fields = mount.split(' ');
+        let _mount_id = fields.next()?;
+        let _parent_id = fields.next()?;
+        let (major, minor) = fields.next()?.split_once(':')?;
+        let root = unescape(fields.next()?);
+        let mount_point = unescape(fields.next()?);
+        let options = fields.next()?.to_string();
+
+        let mut fields = fs.split(' ');
+        let fs_type = fields.next()?.to_string();
+        let source = unescape(fields.next()?);
+        let super_options = fields.next().unwrap_or_default().to_string();
+
+        Some(Self {
+            dev: Dev::new(major.parse().ok()?, minor.parse().ok()?),
+            root,
+            mount_point,
+            options,
+            fs_type,
+            source,
+            super_options,
+        })
+    }
+
+    /// Directory of an overlayfs layer, `layer` numbered like `FileAccessEvent::ovl_layer`.
+    pub fn overlay_layer_dir(&self, layer: u8) -> Option<&str> {
+        let option = |name: &str| {
+            self.super_options
+                .split(',')
+                .find_map(|option| option.strip_prefix(name))
+        };
+        match layer {
+            0 => None,
+            1 => option("upperdir="),
+            // `::` separates data-only lower dirs
+            layer => option("lowerdir=")?
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .nth(layer as usize - 2),
+        }
+    }
+
+    /// Server and export of a network filesystem: `host:/export` for NFS, Ceph and sshfs,
+    /// `//host/share` for SMB.
+    pub fn remote(&self) -> Option<(&str, &str)> {
+        if let Some(unc) = self.source.strip_prefix("//") {
+            let (host, share) = unc.split_once('/').unwrap_or((unc, ""));
+            return Some((host, share));
+        }
+
+        let (host, export) = self.source.rsplit_once(":/")?;
+        // `[fe80::1]:/export`, `mon1,mon2:6789:/path`, `sshfs#user@host:/path`
+        let host = host.rsplit(['#', '@']).next().unwrap_or(host);
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        Some((host, &self.source[self.source.len() - export.len() - 1..]))
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        match path.strip_prefix(self.mount_point.as_str()) {
+            Some(rest) => self.mount_point == "/" || rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    }
+}
+
+/// Undoes the octal escaping of spaces, tabs, newlines and backslashes.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|oct| std::str::from_utf8(oct).ok())
+            .and_then(|oct| u8::from_str_radix(oct, 8).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Mounts indexed by device, anonymous devices (tmpfs, overlayfs, NFS, btrfs subvolumes)
+/// included.
+#[derive(Debug, Default)]
+pub struct MountTable {
+    by_dev: HashMap<Dev, Vec<Arc<Mount>>>,
+}
+
+impl MountTable {
+    pub fn parse(content: &str) -> Self {
+        let mut by_dev: HashMap<Dev, Vec<Arc<Mount>>> = HashMap::new();
+        for mount in content.lines().filter_map(Mount::parse) {
+            by_dev.entry(mount.dev).or_default().push(Arc::new(mount));
+        }
+        Self { by_dev }
+    }
+
+    pub fn read() -> Result<Self> {
+        match std::fs::read_to_string(MOUNTINFO) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(err) => Err(types::Error::Path(err, MOUNTINFO.to_string())),
+        }
+    }
+
+    /// Mount of `dev` that `path` is under, the innermost one if the device is mounted several
+    /// times. Falls back to the first mount of the device for paths that were resolved in
+    /// another mount namespace.
+    pub fn lookup(&self, dev: Dev, path: &str) -> Option<Arc<Mount>> {
+        let mounts = self.by_dev.get(&dev)?;
+        mounts
+            .iter()
+            .filter(|mount| mount.contains(path))
+            .max_by_key(|mount| mount.mount_point.len())
+            .or_else(|| mounts.first())
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO_SAMPLE: &str = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw,errors=remount-ro
+23 22 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:12 - proc proc rw
+35 22 259:1 / /boot/efi rw,relatime shared:29 - vfat /dev/nvme0n1p1 rw,fmask=0077,dmask=0077
+61 22 259:2 /srv/data /mnt/my\\040data rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw,errors=remount-ro
+75 22 0:48 / /mnt/nfs rw,relatime shared:40 - nfs4 nas.lan:/export/home rw,vers=4.2,addr=10.0.0.5
+812 790 0:63 / /var/lib/docker/overlay2/abc/merged rw,relatime - overlay overlay rw,lowerdir=/var/lib/docker/overlay2/l/A:/var/lib/docker/overlay2/l/B,upperdir=/var/lib/docker/overlay2/abc/diff,workdir=/var/lib/docker/overlay2/abc/work
+";
+
+    fn mount(line: &str) -> Mount {
+        Mount::parse(line).unwrap()
+    }
+
+    #[test]
+    fn parse_line() {
+        let mount = mount(MOUNTINFO_SAMPLE.lines().next().unwrap());
+        assert_eq!(mount.dev, Dev::new(259, 2));
+        assert_eq!(mount.root, "/");
+        assert_eq!(mount.mount_point, "/");
+        assert_eq!(mount.options, "rw,relatime");
+        assert_eq!(mount.fs_type, "ext4");
+        assert_eq!(mount.source, "/dev/nvme0n1p2");
+        assert_eq!(mount.super_options, "rw,errors=remount-ro");
+    }
+
+    #[test]
+    fn parse_optional_fields() {
+        // none, one, and several optional fields before the `-` terminator
+        let none = mount("812 790 0:63 / /merged rw,relatime - overlay overlay rw");
+        assert_eq!(none.options, "rw,relatime");
+        assert_eq!(none.fs_type, "overlay");
+        let several = mount(
+            "36 35 98:0 /mnt1 /mnt2 rw,noatime shared:5 master:1 propagate_from:2 - ext3 /dev/root rw,errors=continue",
+        );
+        assert_eq!(several.root, "/mnt1");
+        assert_eq!(several.mount_point, "/mnt2");
+        assert_eq!(several.fs_type, "ext3");
+        assert_eq!(several.source, "/dev/root");
+        // a dash inside a field is not the terminator
+        let dashed = mount("40 22 253:3 / /srv/web-data rw - xfs /dev/mapper/vg0-web rw");
+        assert_eq!(dashed.mount_point, "/srv/web-data");
+        assert_eq!(dashed.source, "/dev/mapper/vg0-web");
+        // no super options
+        assert_eq!(
+            mount("40 22 0:50 / /mnt rw - tmpfs tmpfs").super_options,
+            ""
+        );
+    }
+
+    #[test]
+    fn parse_malformed() {
+        assert_eq!(Mount::parse(""), None);
+        assert_eq!(Mount::parse("22 1 259:2 / / rw,relatime shared:1"), None);
+        assert_eq!(Mount::parse("22 1 259 / / rw - ext4 /dev/sda1 rw"), None);
+    }
+
+    #[test]
+    fn unescape_octal() {
+        assert_eq!(unescape("/mnt/my\\040data"), "/mnt/my data");
+        assert_eq!(unescape("/mnt/a\\011b\\012c"), "/mnt/a\tb\nc");
+        assert_eq!(unescape("/mnt/back\\134slash"), "/mnt/back\\slash");
+        // not an escape, or cut short at the end
+        assert_eq!(unescape("/mnt/x\\9y"), "/mnt/x\\9y");
+        assert_eq!(unescape("/mnt/x\\04"), "/mnt/x\\04");
+        assert_eq!(unescape("/mnt/plain"), "/mnt/plain");
+        assert_eq!(
+            mount(MOUNTINFO_SAMPLE.lines().nth(3).unwrap()).mount_point,
+            "/mnt/my data"
+        );
+    }
+
+    #[test]
+    fn lookup_innermost() {
+        let table = MountTable::parse(MOUNTINFO_SAMPLE);
+        let root_dev = Dev::new(259, 2);
+        assert_eq!(
+            table.lookup(root_dev, "/mnt/my data/file").unwrap().root,
+            "/srv/data"
+        );
+        assert_eq!(
+            table.lookup(root_dev, "/etc/passwd").unwrap().mount_point,
+            "/"
+        );
+        // `/mnt/my dataset` is not below `/mnt/my data`
+        assert_eq!(
+            table
+                .lookup(root_dev, "/mnt/my dataset")
+                .unwrap()
+                .mount_point,
+            "/"
+        );
+        assert_eq!(table.lookup(Dev::new(8, 0), "/"), None);
+    }
+
+    #[test]
+    fn overlay_layer_dirs() {
+        let overlay = mount(MOUNTINFO_SAMPLE.lines().nth(5).unwrap());
+        assert_eq!(overlay.overlay_layer_dir(0), None);
+        assert_eq!(
+            overlay.overlay_layer_dir(1),
+            Some("/var/lib/docker/overlay2/abc/diff")
+        );
+        assert_eq!(
+            overlay.overlay_layer_dir(2),
+            Some("/var/lib/docker/overlay2/l/A")
+        );
+        assert_eq!(
+            overlay.overlay_layer_dir(3),
+            Some("/var/lib/docker/overlay2/l/B")
+        );
+        assert_eq!(overlay.overlay_layer_dir(4), None);
+
+        let data_only = mount("90 22 0:70 / /merged rw - overlay overlay rw,lowerdir=/l1::/data1");
+        assert_eq!(data_only.overlay_layer_dir(1), None);
+        assert_eq!(data_only.overlay_layer_dir(2), Some("/l1"));
+        assert_eq!(data_only.overlay_layer_dir(3), Some("/data1"));
+    }
+
+    #[test]
+    fn remote() {
+        let nfs = mount(MOUNTINFO_SAMPLE.lines().nth(4).unwrap());
+        assert_eq!(nfs.remote(), Some(("nas.lan", "/export/home")));
+
+        let with_source = |source: &str| Mount {
+            source: source.to_string(),
+            ..nfs.clone()
+        };
+        assert_eq!(
+            with_source("[fe80::1]:/export").remote(),
+            Some(("fe80::1", "/export"))
+        );
+        assert_eq!(
+            with_source("sshfs#user@host:/home/user").remote(),
+            Some(("host", "/home/user"))
+        );
+        assert_eq!(with_source("user@host:/").remote(), Some(("host", "/")));
+        assert_eq!(
+            with_source("//fileserver/share").remote(),
+            Some(("fileserver", "share"))
+        );
+        assert_eq!(
+            with_source("//fileserver").remote(),
+            Some(("fileserver", ""))
+        );
+        assert_eq!(with_source("/dev/sda1").remote(), None);
+        assert_eq!(with_source("tmpfs").remote(), None);
+    }
+}
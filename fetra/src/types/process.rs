@@ -0,0 +1,181 @@
+use crate::types;
+use crate::types::Result;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Deeper trees are cut off, the top-level ancestor is then unknown.
+const MAX_ANCESTORS: usize = 64;
+
+/// Processes that only start sessions or supervise others, never the command the work was
+/// started by. Comms are cut at 15 bytes, as in `containerd-shim` for `containerd-shim-runc-v2`.
+const SESSION_COMMS: &[&str] = &[
+    "systemd",
+    "sshd",
+    "sshd-session",
+    "login",
+    "agetty",
+    "su",
+    "sudo",
+    "tmux: server",
+    "screen",
+    "SCREEN",
+    "cron",
+    "crond",
+    "containerd-shim",
+    "conmon",
+    "tini",
+    "dumb-init",
+    "sh",
+    "bash",
+    "dash",
+    "zsh",
+    "fish",
+];
+
+/// Process id in the pid namespace of `/proc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Pid(pub u32);
+
+/// Fields of `/proc/<pid>/stat` needed to walk up the process tree.
+#[derive(Debug, Clone)]
+pub struct Stat {
+    pub comm: String,
+    pub ppid: u32,
+}
+
+impl Pid {
+    /// Path of the executable, suffixed with ` (deleted)` if it was replaced since.
+    pub async fn exe(&self) -> Result<String> {
+        let path = format!("/proc/{}/exe", self.0);
+        match tokio::fs::read_link(&path).await {
+            Ok(exe) => Ok(exe.to_string_lossy().into_owned()),
+            Err(err) => Err(types::Error::Path(err, path)),
+        }
+    }
+
+    pub async fn stat(&self) -> Result<Stat> {
+        let path = format!("/proc/{}/stat", self.0);
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(err) => return Err(types::Error::Path(err, path)),
+        };
+
+        // `pid (comm) state ppid ...`, comm may contain spaces and parentheses
+        let (comm, rest) = content
+            .split_once(" (")
+            .and_then(|(_pid, rest)| rest.rsplit_once(") "))
+            .ok_or(types::Error::ProcessNotFound(self.0))?;
+        let ppid = rest
+            .split_whitespace()
+            .nth(1)
+            .and_then(|ppid| ppid.parse().ok())
+            .ok_or(types::Error::ProcessNotFound(self.0))?;
+
+        Ok(Stat {
+            comm: comm.to_string(),
+            ppid,
+        })
+    }
+}
+
+/// Names of the processes above a caller, parent first, init excluded.
+#[derive(Debug, Clone, Default)]
+pub struct Ancestry {
+    comms: Vec<String>,
+}
+
+impl Ancestry {
+    /// Walks up from `ppid` until init or kthreadd. Parents that already exited end the walk
+    /// early, their orphans were reparented anyway.
+    pub async fn from_parent(ppid: u32) -> Self {
+        let mut comms = Vec::new();
+        let mut pid = ppid;
+        while pid > 2 && comms.len() < MAX_ANCESTORS {
+            let Ok(stat) = Pid(pid).stat().await else {
+                break;
+            };
+            comms.push(stat.comm);
+            pid = stat.ppid;
+        }
+        Self { comms }
+    }
+
+    pub fn parent(&self) -> Option<&str> {
+        self.comms.first().map(String::as_str)
+    }
+
+    /// The command the caller runs under: the topmost ancestor below the sessions, shells and
+    /// supervisors it was started from, as `make` in `sshd>bash>make>sh`. `None` when there is
+    /// no such ancestor and the caller is the top-level command itself.
+    pub fn root(&self) -> Option<&str> {
+        self.comms
+            .iter()
+            .rev()
+            .map(String::as_str)
+            .find(|comm| !SESSION_COMMS.contains(comm))
+    }
+
+    /// The whole chain from the root down to the parent, as in `sshd>bash>make`.
+    pub fn chain(&self) -> String {
+        let comms: Vec<&str> = self.comms.iter().rev().map(String::as_str).collect();
+        comms.join(">")
+    }
+}
+
+/// Thread names seen in a process, capped so that processes naming threads after ids
+/// (`pool-1-thread-4711`) don't create a series per thread.
+#[derive(Debug)]
+pub struct ThreadNames {
+    max: usize,
+    names: Mutex<HashSet<String>>,
+}
+
+impl ThreadNames {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            names: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// `name` itself, or `other` once `max` other names were seen.
+    pub fn label(&self, name: &str) -> String {
+        let mut names = self.names.lock().unwrap();
+        if names.contains(name) {
+            return name.to_string();
+        }
+        if names.len() >= self.max {
+            return String::from("other");
+        }
+        names.insert(name.to_string());
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ancestry(chain: &str) -> Ancestry {
+        Ancestry {
+            comms: chain.split('>').rev().map(String::from).collect(),
+        }
+    }
+
+    #[test]
+    fn root_below_sessions() {
+        assert_eq!(ancestry("sshd>sshd>bash>make>sh").root(), Some("make"));
+        assert_eq!(
+            ancestry("systemd>tmux: server>zsh>cargo").root(),
+            Some("cargo")
+        );
+        assert_eq!(ancestry("containerd-shim>java").root(), Some("java"));
+        assert_eq!(ancestry("postgres").root(), Some("postgres"));
+    }
+
+    #[test]
+    fn no_root_below_sessions() {
+        assert_eq!(ancestry("sshd>sshd>bash").root(), None);
+        assert_eq!(Ancestry::default().root(), None);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::types;
+use crate::types::Result;
+use std::ffi::CStr;
+
+/// `getpwuid_r`/`getgrgid_r` keep asking for a bigger buffer with `ERANGE` up to this.
+const MAX_BUF_LEN: usize = 1 << 20;
+
+/// User id, resolved to a name through NSS so LDAP and sssd users are found as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Uid(pub u32);
+
+/// Group id, resolved like [`Uid`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Gid(pub u32);
+
+impl Uid {
+    pub async fn name(&self) -> Result<String> {
+        let uid = self.0;
+        let name = tokio::task::spawn_blocking(move || {
+            lookup(
+                |pwd, buf, len, result| unsafe { libc::getpwuid_r(uid, pwd, buf, len, result) },
+                |pwd: &libc::passwd| pwd.pw_name,
+            )
+        })
+        .await
+        .map_err(std::io::Error::from)??;
+        name.ok_or(types::Error::UserNotFound(uid))
+    }
+}
+
+impl Gid {
+    pub async fn name(&self) -> Result<String> {
+        let gid = self.0;
+        let name = tokio::task::spawn_blocking(move || {
+            lookup(
+                |grp, buf, len, result| unsafe { libc::getgrgid_r(gid, grp, buf, len, result) },
+                |grp: &libc::group| grp.gr_name,
+            )
+        })
+        .await
+        .map_err(std::io::Error::from)??;
+        name.ok_or(types::Error::GroupNotFound(gid))
+    }
+}
+
+/// Runs a reentrant NSS lookup, growing the buffer the entry's strings live in until they fit.
+fn lookup<T>(
+    get: impl Fn(*mut T, *mut libc::c_char, usize, *mut *mut T) -> libc::c_int,
+    name: impl Fn(&T) -> *const libc::c_char,
+) -> Result<Option<String>> {
+    let mut entry: T = unsafe { std::mem::zeroed() };
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        let mut result = std::ptr::null_mut();
+        match get(&mut entry, buf.as_mut_ptr(), buf.len(), &mut result) {
+            0 if result.is_null() => return Ok(None),
+            0 => {
+                let name = unsafe { CStr::from_ptr(name(&entry)) };
+                return Ok(Some(name.to_string_lossy().into_owned()));
+            }
+            libc::ERANGE if buf.len() < MAX_BUF_LEN => buf.resize(buf.len() * 2, 0),
+            err => return Err(std::io::Error::from_raw_os_error(err).into()),
+        }
+    }
+}